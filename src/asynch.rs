@@ -0,0 +1,304 @@
+use core::{
+    future::poll_fn,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use atomic_waker::AtomicWaker;
+
+use crate::{IntEnFlags, IoError, LineStsFlags, Uart16550};
+
+/// Wakes the task waiting on an [`AsyncSerialPort`].
+///
+/// The waker is typically stored in a `static` and shared between the task using the
+/// [`AsyncSerialPort`] and the interrupt handler of the UART.
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[derive(Debug)]
+pub struct SerialWaker {
+    waker: AtomicWaker,
+    masked: AtomicBool,
+}
+
+impl SerialWaker {
+    /// Creates a new waker.
+    pub const fn new() -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+            masked: AtomicBool::new(false),
+        }
+    }
+
+    /// Masks all interrupts of the UART and wakes the waiting task, if any.
+    ///
+    /// This method must be called from the interrupt handler of the UART, with a handle to
+    /// the same UART as the one wrapped by the [`AsyncSerialPort`], e.g. a second
+    /// [`MmioSerialPort`](crate::MmioSerialPort) on the same base address. Wiring the UART
+    /// interrupt to the interrupt controller is up to the user.
+    ///
+    /// The interrupt line of the UART stays asserted until the cause is handled, e.g. the
+    /// received data is read, so the interrupt enable register is cleared to keep a level
+    /// triggered interrupt from firing again before the task ran. The task enables the
+    /// interrupts again when it has to wait the next time. As only this single register is
+    /// written, the task must not change the baud rate while interrupts are enabled.
+    pub fn on_interrupt(&self, port: &mut impl Uart16550) {
+        port.disable_all_interrupts();
+        self.masked.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+}
+
+impl Default for SerialWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An async wrapper around an initialized UART.
+///
+/// Implements [`embedded_io_async::Read`] and [`embedded_io_async::Write`] on top of the
+/// non-blocking `try_*` methods of the wrapped port. Whenever the port would block, the
+/// received data or transmitter empty interrupt is enabled until the port can make progress
+/// again, and the task waits until [`SerialWaker::on_interrupt`] is called.
+///
+/// ```no_run
+/// use uart_16550::{AsyncSerialPort, MmioSerialPort, SerialWaker, Uart16550};
+///
+/// static WAKER: SerialWaker = SerialWaker::new();
+///
+/// // Called by the interrupt controller
+/// fn uart_interrupt_handler() {
+///     let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+///     WAKER.on_interrupt(&mut serial_port);
+/// }
+///
+/// async fn echo() {
+///     use embedded_io_async::{Read, Write};
+///
+///     let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+///     serial_port.init();
+///     let mut serial_port = AsyncSerialPort::new(serial_port, &WAKER);
+///
+///     let mut buf = [0; 16];
+///     let len = serial_port.read(&mut buf).await.unwrap();
+///     serial_port.write_all(&buf[..len]).await.unwrap();
+/// }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[derive(Debug)]
+pub struct AsyncSerialPort<'a, P> {
+    port: P,
+    waker: &'a SerialWaker,
+}
+
+impl<'a, P> AsyncSerialPort<'a, P> {
+    /// Wraps the given initialized port.
+    ///
+    /// The `waker` must be the one that is woken by the interrupt handler of the port.
+    pub fn new(port: P, waker: &'a SerialWaker) -> Self {
+        Self { port, waker }
+    }
+
+    /// Returns a reference to the wrapped port.
+    pub fn inner(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Unwraps the port.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+}
+
+impl<P: Uart16550> AsyncSerialPort<'_, P> {
+    /// Registers the waker and enables the given interrupt.
+    ///
+    /// Also enables the interrupts that were masked by [`SerialWaker::on_interrupt`] again.
+    fn register(&mut self, cx: &mut Context<'_>, int_en: IntEnFlags) {
+        self.waker.waker.register(cx.waker());
+        if self.waker.masked.swap(false, Ordering::Acquire)
+            || !self.port.enabled_interrupts().contains(int_en)
+        {
+            self.port.enable_interrupts(int_en);
+        }
+    }
+
+    /// Disables the given interrupt again.
+    fn unregister(&mut self, int_en: IntEnFlags) {
+        if self.port.enabled_interrupts().intersects(int_en) {
+            self.port.disable_interrupts(int_en);
+        }
+    }
+
+    fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<u8> {
+        if let Ok(data) = self.port.try_receive() {
+            self.unregister(IntEnFlags::RECEIVED);
+            return Poll::Ready(data);
+        }
+        self.register(cx, IntEnFlags::RECEIVED);
+        // Check again in case the data arrived before the waker was registered
+        match self.port.try_receive() {
+            Ok(data) => {
+                self.unregister(IntEnFlags::RECEIVED);
+                Poll::Ready(data)
+            }
+            Err(_) => Poll::Pending,
+        }
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>, data: u8) -> Poll<()> {
+        if self.port.try_send_raw(data).is_ok() {
+            self.unregister(IntEnFlags::SENT);
+            return Poll::Ready(());
+        }
+        self.register(cx, IntEnFlags::SENT);
+        match self.port.try_send_raw(data) {
+            Ok(()) => {
+                self.unregister(IntEnFlags::SENT);
+                Poll::Ready(())
+            }
+            Err(_) => Poll::Pending,
+        }
+    }
+
+    /// Reads all immediately available bytes until `buf` is full, or registers the
+    /// waker of the given context if no data is available.
+    ///
+    /// This is the building block of the [`embedded_io_async::Read`] implementation
+    /// and can be used to implement other async I/O traits on top of this type.
+    ///
+    /// ```no_run
+    /// use core::task::{Context, Poll, Waker};
+    /// use uart_16550::{AsyncSerialPort, MmioSerialPort, SerialWaker, Uart16550};
+    ///
+    /// static WAKER: SerialWaker = SerialWaker::new();
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut serial_port = AsyncSerialPort::new(serial_port, &WAKER);
+    ///
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// let mut buf = [0; 16];
+    /// if let Poll::Ready(len) = serial_port.poll_read(&mut cx, &mut buf) {
+    ///     // handle &buf[..len]
+    /// }
+    /// ```
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<usize> {
+        let Some((first, rest)) = buf.split_first_mut() else {
+            return Poll::Ready(0);
+        };
+        match self.poll_receive(cx) {
+            Poll::Ready(data) => *first = data,
+            Poll::Pending => return Poll::Pending,
+        }
+        Poll::Ready(1 + self.port.receive_into(rest))
+    }
+
+    /// Sends bytes as long as the UART accepts them without blocking, or registers
+    /// the waker of the given context if not even one byte could be sent.
+    ///
+    /// Completes with [`IoError::TxDisabled`] instead of waiting if the transmitter was
+    /// disabled in software. This is the building block of the [`embedded_io_async::Write`]
+    /// implementation.
+    ///
+    /// ```no_run
+    /// use core::task::{Context, Poll, Waker};
+    /// use uart_16550::{AsyncSerialPort, MmioSerialPort, SerialWaker, Uart16550};
+    ///
+    /// static WAKER: SerialWaker = SerialWaker::new();
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut serial_port = AsyncSerialPort::new(serial_port, &WAKER);
+    ///
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// match serial_port.poll_write(&mut cx, b"Hello") {
+    ///     Poll::Ready(Ok(len)) => {
+    ///         // len bytes were sent
+    ///     }
+    ///     Poll::Ready(Err(err)) => {
+    ///         // the transmitter was disabled
+    ///     }
+    ///     Poll::Pending => {
+    ///         // the waker is woken once the transmitter is empty
+    ///     }
+    /// }
+    /// ```
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, IoError>> {
+        let Some((&first, rest)) = buf.split_first() else {
+            return Poll::Ready(Ok(0));
+        };
+        if !self.port.tx_enabled() {
+            return Poll::Ready(Err(IoError::TxDisabled));
+        }
+        if self.poll_send(cx, first).is_pending() {
+            return Poll::Pending;
+        }
+        let mut count = 1;
+        for &data in rest {
+            if self.port.try_send_raw(data).is_err() {
+                break;
+            }
+            count += 1;
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    /// Completes once all bytes were shifted out, or registers the waker of the given
+    /// context.
+    ///
+    /// The UART only raises an interrupt when the transmitter holding register becomes
+    /// empty, so the waker is woken right away while the last byte is still being shifted
+    /// out, which takes at most one character time.
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let line_sts = self.port.line_status();
+        if line_sts.contains(LineStsFlags::TRANSMITTER_EMPTY) {
+            self.unregister(IntEnFlags::SENT);
+            return Poll::Ready(());
+        }
+        if line_sts.contains(LineStsFlags::OUTPUT_EMPTY) {
+            self.unregister(IntEnFlags::SENT);
+        } else {
+            self.register(cx, IntEnFlags::SENT);
+            // Check again in case the holding register emptied before the waker was registered
+            if !self.port.line_status().contains(LineStsFlags::OUTPUT_EMPTY) {
+                return Poll::Pending;
+            }
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(not(feature = "embedded-io"))]
+impl embedded_io_async::Error for IoError {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        embedded_io_async::ErrorKind::Other
+    }
+}
+
+impl<P: Uart16550> embedded_io_async::ErrorType for AsyncSerialPort<'_, P> {
+    type Error = IoError;
+}
+
+impl<P: Uart16550> embedded_io_async::Read for AsyncSerialPort<'_, P> {
+    /// Waits for at least one byte, then reads all immediately available bytes
+    /// until `buf` is full.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(poll_fn(|cx| self.poll_read(cx, buf)).await)
+    }
+}
+
+impl<P: Uart16550> embedded_io_async::Write for AsyncSerialPort<'_, P> {
+    /// Waits until at least one byte was sent, then sends bytes as long as the UART
+    /// accepts them without blocking.
+    ///
+    /// Returns [`IoError::TxDisabled`] if the transmitter was disabled in software.
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        poll_fn(|cx| self.poll_flush(cx)).await;
+        Ok(())
+    }
+}
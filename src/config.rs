@@ -0,0 +1,286 @@
+use crate::{divisor_for, ConfigError, DEFAULT_BAUD_RATE};
+
+/// Mask of the word length bits in the line control register.
+pub(crate) const LCR_DATA_BITS_MASK: u8 = 0b11;
+
+/// Mask of the parity bits in the line control register.
+pub(crate) const LCR_PARITY_MASK: u8 = 0b0011_1000;
+
+/// Stop bit selection bit in the line control register.
+pub(crate) const LCR_STOP_BITS: u8 = 1 << 2;
+
+/// Break control bit in the line control register.
+pub(crate) const LCR_BREAK: u8 = 1 << 6;
+
+/// Line control register value that opens the enhanced feature register window of 16550
+/// clones.
+pub(crate) const ENHANCED_REGISTER_LCR: u8 = 0xBF;
+
+/// FIFO enable bit in the FIFO control register.
+pub(crate) const FCR_ENABLE: u8 = 1;
+
+/// Self-clearing bit in the FIFO control register that clears the receive FIFO.
+pub(crate) const FCR_CLEAR_RX: u8 = 1 << 1;
+
+/// Self-clearing bit in the FIFO control register that clears the transmit FIFO.
+pub(crate) const FCR_CLEAR_TX: u8 = 1 << 2;
+
+/// Number of data bits per character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataBits {
+    /// 5 data bits.
+    Five,
+    /// 6 data bits.
+    Six,
+    /// 7 data bits.
+    Seven,
+    /// 8 data bits.
+    Eight,
+}
+
+impl DataBits {
+    /// Encoding in the line control register.
+    pub(crate) const fn lcr_bits(self) -> u8 {
+        match self {
+            Self::Five => 0b00,
+            Self::Six => 0b01,
+            Self::Seven => 0b10,
+            Self::Eight => 0b11,
+        }
+    }
+
+    /// Decodes the word length bits of the given line control register value.
+    pub(crate) const fn from_lcr(lcr: u8) -> Self {
+        match lcr & LCR_DATA_BITS_MASK {
+            0b00 => Self::Five,
+            0b01 => Self::Six,
+            0b10 => Self::Seven,
+            _ => Self::Eight,
+        }
+    }
+}
+
+/// Parity mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Parity {
+    /// No parity bit.
+    None,
+    /// Odd parity.
+    Odd,
+    /// Even parity.
+    Even,
+    /// Parity bit is always 1.
+    Mark,
+    /// Parity bit is always 0.
+    Space,
+}
+
+impl Parity {
+    /// Encoding in the line control register.
+    ///
+    /// Bit 3 enables parity, bit 4 selects even parity and bit 5 enables stick parity.
+    pub(crate) const fn lcr_bits(self) -> u8 {
+        match self {
+            Self::None => 0b000 << 3,
+            Self::Odd => 0b001 << 3,
+            Self::Even => 0b011 << 3,
+            Self::Mark => 0b101 << 3,
+            Self::Space => 0b111 << 3,
+        }
+    }
+
+    /// Decodes the parity bits of the given line control register value.
+    pub(crate) const fn from_lcr(lcr: u8) -> Self {
+        match (lcr & LCR_PARITY_MASK) >> 3 {
+            0b001 => Self::Odd,
+            0b011 => Self::Even,
+            0b101 => Self::Mark,
+            0b111 => Self::Space,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Number of stop bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StopBits {
+    /// 1 stop bit.
+    One,
+    /// 1.5 stop bits, only available with 5 data bits.
+    OneAndHalf,
+    /// 2 stop bits, only available with 6, 7 or 8 data bits.
+    Two,
+}
+
+impl StopBits {
+    /// Encoding in the line control register for the given word length.
+    ///
+    /// The hardware uses the same bit for 1.5 and 2 stop bits, depending on whether the word
+    /// length is 5 bits or longer.
+    pub(crate) fn lcr_bits(self, lcr: u8) -> Result<u8, ConfigError> {
+        let five_data_bits = lcr & LCR_DATA_BITS_MASK == DataBits::Five.lcr_bits();
+        match (self, five_data_bits) {
+            (Self::One, _) => Ok(0),
+            (Self::OneAndHalf, true) | (Self::Two, false) => Ok(LCR_STOP_BITS),
+            _ => Err(ConfigError::UnsupportedStopBits),
+        }
+    }
+
+    /// Decodes the stop bits of the given line control register value.
+    pub(crate) const fn from_lcr(lcr: u8) -> Self {
+        match (lcr & LCR_STOP_BITS != 0, DataBits::from_lcr(lcr)) {
+            (false, _) => Self::One,
+            (true, DataBits::Five) => Self::OneAndHalf,
+            (true, _) => Self::Two,
+        }
+    }
+}
+
+/// Receive FIFO interrupt trigger level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FifoTrigger {
+    /// Trigger at 1 byte.
+    Bytes1,
+    /// Trigger at 4 bytes.
+    Bytes4,
+    /// Trigger at 8 bytes.
+    Bytes8,
+    /// Trigger at 14 bytes.
+    Bytes14,
+}
+
+impl FifoTrigger {
+    /// Encoding in the FIFO control register.
+    pub(crate) const fn fcr_bits(self) -> u8 {
+        match self {
+            Self::Bytes1 => 0b00 << 6,
+            Self::Bytes4 => 0b01 << 6,
+            Self::Bytes8 => 0b10 << 6,
+            Self::Bytes14 => 0b11 << 6,
+        }
+    }
+}
+
+/// Flow control mode of the transmit and receive paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlowControl {
+    /// No flow control.
+    None,
+    /// Hardware flow control through the `RTS` and `CTS` lines, managed in software.
+    RtsCts,
+}
+
+/// A complete line configuration.
+///
+/// The configuration is built with a builder-style API and applied at once through
+/// `configure`:
+///
+/// ```
+/// use uart_16550::{DataBits, LineConfig, Parity, StopBits};
+///
+/// let config = LineConfig::new()
+///     .baud(115200)
+///     .data_bits(DataBits::Eight)
+///     .parity(Parity::None)
+///     .stop_bits(StopBits::One);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LineConfig {
+    baud: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+}
+
+impl LineConfig {
+    /// Creates the default configuration of [38400/8-N-1](https://en.wikipedia.org/wiki/8-N-1).
+    pub const fn new() -> Self {
+        Self {
+            baud: DEFAULT_BAUD_RATE,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+
+    /// Sets the baud rate.
+    pub const fn baud(self, baud: u32) -> Self {
+        Self { baud, ..self }
+    }
+
+    /// Sets the number of data bits per character.
+    pub const fn data_bits(self, data_bits: DataBits) -> Self {
+        Self { data_bits, ..self }
+    }
+
+    /// Sets the parity mode.
+    pub const fn parity(self, parity: Parity) -> Self {
+        Self { parity, ..self }
+    }
+
+    /// Sets the number of stop bits.
+    pub const fn stop_bits(self, stop_bits: StopBits) -> Self {
+        Self { stop_bits, ..self }
+    }
+
+    /// Returns the baud rate.
+    ///
+    /// ```
+    /// use uart_16550::{LineConfig, Parity};
+    ///
+    /// let config = LineConfig::new().baud(115_200).parity(Parity::Even);
+    /// assert_eq!(config.baud_rate(), 115_200);
+    /// assert_eq!(config.get_parity(), Parity::Even);
+    /// ```
+    pub const fn baud_rate(self) -> u32 {
+        self.baud
+    }
+
+    /// Returns the number of data bits per character.
+    pub const fn get_data_bits(self) -> DataBits {
+        self.data_bits
+    }
+
+    /// Returns the parity mode.
+    pub const fn get_parity(self) -> Parity {
+        self.parity
+    }
+
+    /// Returns the number of stop bits.
+    pub const fn get_stop_bits(self) -> StopBits {
+        self.stop_bits
+    }
+
+    /// Decodes the given line control register value, combined with the given baud rate.
+    pub(crate) const fn from_lcr(baud: u32, lcr: u8) -> Self {
+        Self {
+            baud,
+            data_bits: DataBits::from_lcr(lcr),
+            parity: Parity::from_lcr(lcr),
+            stop_bits: StopBits::from_lcr(lcr),
+        }
+    }
+
+    /// Computes the divisor latch value for the given input clock.
+    pub(crate) fn divisor(&self, clock_hz: u32) -> Result<u16, ConfigError> {
+        divisor_for(clock_hz, self.baud)
+    }
+
+    /// Computes the line control register value.
+    pub(crate) fn line_ctrl(&self) -> Result<u8, ConfigError> {
+        let line_ctrl = self.data_bits.lcr_bits() | self.parity.lcr_bits();
+        Ok(line_ctrl | self.stop_bits.lcr_bits(line_ctrl)?)
+    }
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
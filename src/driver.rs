@@ -0,0 +1,233 @@
+use crate::{
+    config::{FCR_CLEAR_RX, FCR_CLEAR_TX, FCR_ENABLE, LCR_DATA_BITS_MASK},
+    spin_for, FifoStatus, FifoTrigger, FlowControl, Iir, IntEnFlags, LineStsFlags, ModemCtrlFlags,
+    ModemStsFlags, UartVariant, DEFAULT_BAUD_RATE, DEFAULT_BAUD_SETTLE_SPINS, DEFAULT_CLOCK_HZ,
+};
+
+/// Offset of a UART register, in multiples of the register stride.
+///
+/// The offset can only be constructed inside this crate, so the register accesses of
+/// [`Driver`] can not be used to bypass `read_register` and `write_register`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register(pub(crate) u8);
+
+pub(crate) const DATA: Register = Register(0);
+pub(crate) const INT_EN: Register = Register(1);
+pub(crate) const FIFO_CTRL: Register = Register(2);
+pub(crate) const INT_ID: Register = Register(2);
+pub(crate) const LINE_CTRL: Register = Register(3);
+pub(crate) const MODEM_CTRL: Register = Register(4);
+pub(crate) const LINE_STS: Register = Register(5);
+pub(crate) const MODEM_STS: Register = Register(6);
+pub(crate) const SCRATCH: Register = Register(7);
+
+/// Software state of a UART, including copies of its write-only registers.
+#[derive(Debug)]
+pub struct State {
+    pub(crate) tx_enabled: bool,
+    pub(crate) translate: bool,
+    pub(crate) baud_settle_spins: u32,
+    pub(crate) clock_hz: u32,
+    pub(crate) baud: u32,
+    pub(crate) divisor: u16,
+    pub(crate) int_en: IntEnFlags,
+    pub(crate) line_ctrl: u8,
+    pub(crate) modem_ctrl: ModemCtrlFlags,
+    pub(crate) fifo_ctrl: u8,
+    pub(crate) fifo_depth: usize,
+    pub(crate) variant: Option<UartVariant>,
+    pub(crate) flow_control: FlowControl,
+    pub(crate) reset_on_overrun: bool,
+    pub(crate) mask_received: bool,
+    pub(crate) relax: fn(),
+    pub(crate) relax_interval: u32,
+}
+
+impl State {
+    /// The state of a port that was not configured yet.
+    pub(crate) const fn new() -> Self {
+        Self {
+            tx_enabled: true,
+            translate: true,
+            baud_settle_spins: DEFAULT_BAUD_SETTLE_SPINS,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            baud: DEFAULT_BAUD_RATE,
+            divisor: 0,
+            int_en: IntEnFlags::empty(),
+            line_ctrl: 0,
+            modem_ctrl: ModemCtrlFlags::empty(),
+            fifo_ctrl: 0,
+            fifo_depth: 1,
+            variant: None,
+            flow_control: FlowControl::None,
+            reset_on_overrun: false,
+            mask_received: false,
+            relax: core::hint::spin_loop,
+            relax_interval: 1,
+        }
+    }
+}
+
+/// Register access and state of a UART, on top of which [`Uart16550`](crate::Uart16550)
+/// implements the driver.
+///
+/// This trait is not reachable from outside the crate, which seals `Uart16550`.
+pub trait Driver {
+    /// Reads the given register.
+    fn read(&mut self, reg: Register) -> u8;
+
+    /// Writes the given register.
+    fn write(&mut self, reg: Register, value: u8);
+
+    /// Returns the software state.
+    fn state(&self) -> &State;
+
+    /// Returns the software state for modification.
+    fn state_mut(&mut self) -> &mut State;
+}
+
+/// Register helpers shared by the methods of [`Uart16550`](crate::Uart16550).
+///
+/// Unlike [`Driver`], this is no supertrait of `Uart16550`, so the helpers can not be used
+/// to bypass `write_register` from outside the crate.
+pub(crate) trait DriverExt: Driver {
+    /// Reads the line status register.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn line_sts(&mut self) -> LineStsFlags {
+        LineStsFlags::from_bits_truncate(self.read(LINE_STS))
+    }
+
+    /// Reads the data register, masked to the word length if configured.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn read_data(&mut self) -> u8 {
+        let data = self.read(DATA);
+        let state = self.state();
+        if state.mask_received {
+            let bits = 5 + (state.line_ctrl & LCR_DATA_BITS_MASK);
+            data & (0xFF >> (8 - bits))
+        } else {
+            data
+        }
+    }
+
+    /// Calls the relax function if the given waiting iteration completes an interval.
+    fn spin_wait(&self, iteration: u32) {
+        let state = self.state();
+        if iteration % state.relax_interval == state.relax_interval - 1 {
+            (state.relax)();
+        }
+    }
+
+    /// Writes the line control register.
+    fn set_line_ctrl(&mut self, value: u8) {
+        self.write(LINE_CTRL, value);
+        self.state_mut().line_ctrl = value;
+    }
+
+    /// Programs the divisor latch and the line control register.
+    fn set_divisor_and_line_ctrl(&mut self, divisor: u16, line_ctrl: u8) {
+        // Enable DLAB
+        self.write(LINE_CTRL, line_ctrl | 0x80);
+
+        // Configure DLL and DLM
+        self.write(DATA, divisor as u8);
+        self.write(INT_EN, (divisor >> 8) as u8);
+
+        // Disable DLAB and apply the line settings
+        self.write(LINE_CTRL, line_ctrl);
+        let state = self.state_mut();
+        state.line_ctrl = line_ctrl;
+        state.divisor = divisor;
+
+        // Give the baud rate generator time to settle
+        spin_for(state.baud_settle_spins);
+    }
+
+    /// Updates the divisor and baud rate reported by `current_config` to the given divisor.
+    fn update_baud(&mut self, divisor: u16) {
+        let state = self.state_mut();
+        state.baud = state
+            .clock_hz
+            .checked_div(16 * u32::from(divisor))
+            .unwrap_or(0);
+        state.divisor = divisor;
+    }
+
+    /// Writes the interrupt enable register.
+    fn set_int_en(&mut self, flags: IntEnFlags) {
+        self.write(INT_EN, flags.bits());
+        self.state_mut().int_en = flags;
+    }
+
+    /// Writes the FIFO control register.
+    ///
+    /// The self-clearing bits are not recorded in the shadow copy. Disabling the FIFOs
+    /// drops the FIFO depth to 1, while enabling them only takes effect once the interrupt
+    /// identification register confirmed that they work.
+    fn set_fifo_ctrl(&mut self, value: u8) {
+        self.write(FIFO_CTRL, value);
+        let state = self.state_mut();
+        state.fifo_ctrl = value & !(FCR_CLEAR_RX | FCR_CLEAR_TX);
+        if value & FCR_ENABLE == 0 {
+            state.fifo_depth = 1;
+        }
+    }
+
+    /// Reads the interrupt identification register and updates the FIFO depth from the
+    /// FIFO status it reports.
+    fn read_iir(&mut self) -> Iir {
+        let iir = Iir(self.read(INT_ID));
+        self.state_mut().fifo_depth = match iir.fifo_status() {
+            FifoStatus::Enabled => 16,
+            FifoStatus::Unusable | FifoStatus::Disabled => 1,
+        };
+        iir
+    }
+
+    /// Writes the modem control register.
+    fn set_modem_ctrl(&mut self, flags: ModemCtrlFlags) {
+        self.write(MODEM_CTRL, flags.bits());
+        self.state_mut().modem_ctrl = flags;
+    }
+
+    /// Enables the FIFOs and asserts the modem control lines.
+    fn init_fifo_and_modem_ctrl(&mut self) {
+        // Enable FIFO, clear TX/RX queues and
+        // set interrupt watermark at 14 bytes
+        self.set_fifo_ctrl(
+            FCR_ENABLE | FCR_CLEAR_RX | FCR_CLEAR_TX | FifoTrigger::Bytes14.fcr_bits(),
+        );
+
+        // Check that the FIFOs actually work
+        self.read_iir();
+
+        // Mark data terminal ready, signal request to send
+        // and enable auxilliary output #2 (used as interrupt line for CPU)
+        self.set_modem_ctrl(ModemCtrlFlags::DTR | ModemCtrlFlags::RTS | ModemCtrlFlags::OUT2);
+    }
+
+    /// Lowers `RTS` while the receive buffer is full and raises it otherwise, if enabled.
+    fn update_rts(&mut self, buffer_full: bool) {
+        let mut flags = self.state().modem_ctrl;
+        if self.state().flow_control == FlowControl::RtsCts
+            && flags.contains(ModemCtrlFlags::RTS) == buffer_full
+        {
+            flags.set(ModemCtrlFlags::RTS, !buffer_full);
+            self.set_modem_ctrl(flags);
+        }
+    }
+
+    /// Whether the remote allows sending, according to the flow control mode.
+    fn clear_to_send(&mut self) -> bool {
+        match self.state().flow_control {
+            FlowControl::None => true,
+            FlowControl::RtsCts => {
+                ModemStsFlags::from_bits_retain(self.read(MODEM_STS)).contains(ModemStsFlags::CTS)
+            }
+        }
+    }
+}
+
+impl<D: Driver + ?Sized> DriverExt for D {}
@@ -0,0 +1,59 @@
+use core::convert::Infallible;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::SerialPort;
+use crate::{GenericSerialPort, MmioSerialPort, RegisterAccess, Uart16550, WouldBlockError};
+
+macro_rules! impl_genio {
+    ($port:ty $(, $param:ident: $bound:path)?) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "genio")))]
+        impl$(<$param: $bound>)? genio::Read for $port {
+            type ReadError = WouldBlockError;
+
+            /// Reads all immediately available bytes until `buf` is full, failing with
+            /// [`WouldBlockError`] if no data is available.
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::ReadError> {
+                let Some((first, rest)) = buf.split_first_mut() else {
+                    return Ok(0);
+                };
+                *first = self.try_receive()?;
+                Ok(1 + self.receive_into(rest))
+            }
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "genio")))]
+        impl$(<$param: $bound>)? genio::Write for $port {
+            type WriteError = WouldBlockError;
+            type FlushError = Infallible;
+
+            /// Sends bytes as long as the UART accepts them without blocking, failing with
+            /// [`WouldBlockError`] if not even one byte could be sent.
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::WriteError> {
+                let Some((&first, rest)) = buf.split_first() else {
+                    return Ok(0);
+                };
+                self.try_send_raw(first)?;
+                let mut count = 1;
+                for &data in rest {
+                    if self.try_send_raw(data).is_err() {
+                        break;
+                    }
+                    count += 1;
+                }
+                Ok(count)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::FlushError> {
+                Uart16550::flush(self);
+                Ok(())
+            }
+
+            fn size_hint(&mut self, _bytes: usize) {}
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_genio!(SerialPort);
+impl_genio!(MmioSerialPort);
+impl_genio!(GenericSerialPort<R>, R: RegisterAccess);
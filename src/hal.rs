@@ -0,0 +1,43 @@
+use core::convert::Infallible;
+
+use embedded_hal_nb::{nb, serial};
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::SerialPort;
+use crate::{GenericSerialPort, LineStsFlags, MmioSerialPort, RegisterAccess, Uart16550};
+
+macro_rules! impl_embedded_hal {
+    ($port:ty $(, $param:ident: $bound:path)?) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-hal")))]
+        impl$(<$param: $bound>)? serial::ErrorType for $port {
+            type Error = Infallible;
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-hal")))]
+        impl$(<$param: $bound>)? serial::Read<u8> for $port {
+            fn read(&mut self) -> nb::Result<u8, Self::Error> {
+                self.try_receive().map_err(|_| nb::Error::WouldBlock)
+            }
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-hal")))]
+        impl$(<$param: $bound>)? serial::Write<u8> for $port {
+            fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+                self.try_send_raw(word).map_err(|_| nb::Error::WouldBlock)
+            }
+
+            fn flush(&mut self) -> nb::Result<(), Self::Error> {
+                if self.line_status().contains(LineStsFlags::OUTPUT_EMPTY) {
+                    Ok(())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_embedded_hal!(SerialPort);
+impl_embedded_hal!(MmioSerialPort);
+impl_embedded_hal!(GenericSerialPort<R>, R: RegisterAccess);
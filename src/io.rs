@@ -0,0 +1,190 @@
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::SerialPort;
+use crate::{GenericSerialPort, IoError, LineStsFlags, MmioSerialPort, RegisterAccess, Uart16550};
+
+/// Size of the receive buffer of a [`BufferedSerialPort`], one 16550A FIFO worth of data.
+const BUFFER_SIZE: usize = 16;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+impl embedded_io::Error for IoError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+macro_rules! impl_embedded_io {
+    ($port:ty $(, $param:ident: $bound:path)?) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+        impl$(<$param: $bound>)? embedded_io::ErrorType for $port {
+            type Error = IoError;
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+        impl$(<$param: $bound>)? embedded_io::Read for $port {
+            /// Blocks until at least one byte was received, then reads all immediately
+            /// available bytes until `buf` is full.
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+                buf[0] = self.receive();
+                Ok(1 + self.receive_into(&mut buf[1..]))
+            }
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+        impl$(<$param: $bound>)? embedded_io::ReadReady for $port {
+            fn read_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.line_status().contains(LineStsFlags::INPUT_FULL))
+            }
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+        impl$(<$param: $bound>)? embedded_io::Write for $port {
+            /// Blocks until the transmitter is empty, then sends up to a FIFO worth of bytes
+            /// without waiting in between.
+            ///
+            /// Returns [`IoError::TxDisabled`] if the transmitter was disabled in software.
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+                if !self.tx_enabled() {
+                    return Err(IoError::TxDisabled);
+                }
+                let len = buf.len().min(self.fifo_depth());
+                self.send_bytes(&buf[..len]);
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> Result<(), Self::Error> {
+                Uart16550::flush(self);
+                Ok(())
+            }
+        }
+
+        #[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+        impl$(<$param: $bound>)? embedded_io::WriteReady for $port {
+            fn write_ready(&mut self) -> Result<bool, Self::Error> {
+                Ok(self.line_status().contains(LineStsFlags::OUTPUT_EMPTY))
+            }
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_embedded_io!(SerialPort);
+impl_embedded_io!(MmioSerialPort);
+impl_embedded_io!(GenericSerialPort<R>, R: RegisterAccess);
+
+/// A wrapper around a UART that buffers received data.
+///
+/// Implements [`embedded_io::BufRead`], e.g. for reading line by line, by receiving up to a
+/// FIFO worth of data at once into a small internal buffer. Writes are passed through to the
+/// wrapped port.
+///
+/// ```no_run
+/// use embedded_io::BufRead;
+/// use uart_16550::{BufferedSerialPort, MmioSerialPort, Uart16550};
+///
+/// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+/// serial_port.init();
+/// let mut serial_port = BufferedSerialPort::new(serial_port);
+/// let data = serial_port.fill_buf().unwrap();
+/// let len = data.len();
+/// // handle the data
+/// serial_port.consume(len);
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "embedded-io")))]
+#[derive(Debug)]
+pub struct BufferedSerialPort<P> {
+    port: P,
+    buf: [u8; BUFFER_SIZE],
+    pos: usize,
+    len: usize,
+}
+
+impl<P> BufferedSerialPort<P> {
+    /// Wraps the given initialized port.
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            buf: [0; BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the wrapped port.
+    pub fn inner(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Unwraps the port, discarding any buffered data.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    /// Returns the buffered data that was not consumed yet.
+    fn buffered(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+}
+
+impl<P: embedded_io::ErrorType> embedded_io::ErrorType for BufferedSerialPort<P> {
+    type Error = P::Error;
+}
+
+impl<P: embedded_io::Read> embedded_io::Read for BufferedSerialPort<P> {
+    /// Reads buffered data, or blocks until at least one byte was received if the buffer is
+    /// empty.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let data = embedded_io::BufRead::fill_buf(self)?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        embedded_io::BufRead::consume(self, len);
+        Ok(len)
+    }
+}
+
+impl<P: embedded_io::Read> embedded_io::BufRead for BufferedSerialPort<P> {
+    /// Returns the buffered data, or blocks until at least one byte was received if the
+    /// buffer is empty.
+    fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        if self.pos == self.len {
+            self.len = self.port.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(self.buffered())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+}
+
+impl<P: embedded_io::ReadReady> embedded_io::ReadReady for BufferedSerialPort<P> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        if self.buffered().is_empty() {
+            self.port.read_ready()
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+impl<P: embedded_io::Write> embedded_io::Write for BufferedSerialPort<P> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.port.flush()
+    }
+}
+
+impl<P: embedded_io::WriteReady> embedded_io::WriteReady for BufferedSerialPort<P> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.port.write_ready()
+    }
+}
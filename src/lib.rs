@@ -57,44 +57,258 @@
 //! // To receive a byte:
 //! let data = serial_port.receive();
 //! ```
+//!
+//! For early-boot logging from a panic handler, the port can also live in a `static`, as
+//! [`MmioSerialPort::const_new`] is a `const fn`:
+//!
+//! ```no_run
+//! use core::{fmt::Write, ptr::{addr_of_mut, NonNull}};
+//! use uart_16550::{MmioSerialPort, Uart16550};
+//!
+//! static mut SERIAL: MmioSerialPort =
+//!     unsafe { MmioSerialPort::const_new(NonNull::new_unchecked(0x1000_0000 as *mut u8), 1) };
+//!
+//! let serial_port = unsafe { &mut *addr_of_mut!(SERIAL) };
+//! serial_port.init();
+//! writeln!(serial_port, "booting").unwrap();
+//! ```
+//!
+//! ### Logging with `defmt`
+//!
+//! With the `defmt` feature, the public types of this crate implement `defmt::Format`. To
+//! route the `defmt` output of an application over the UART, a global logger can be defined
+//! on top of a static serial port. As `defmt` frames are binary, they must be sent without
+//! translation, e.g. through [`Uart16550::send_bytes`]:
+//!
+//! ```no_run
+//! # #[cfg(feature = "defmt")]
+//! # mod logger {
+//! use core::{
+//!     ptr::{addr_of_mut, NonNull},
+//!     sync::atomic::{AtomicBool, Ordering},
+//! };
+//! use uart_16550::{MmioSerialPort, Uart16550};
+//!
+//! static mut SERIAL: MmioSerialPort =
+//!     unsafe { MmioSerialPort::const_new(NonNull::new_unchecked(0x1000_0000 as *mut u8), 1) };
+//! static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+//! static TAKEN: AtomicBool = AtomicBool::new(false);
+//!
+//! #[defmt::global_logger]
+//! struct Logger;
+//!
+//! unsafe impl defmt::Logger for Logger {
+//!     fn acquire() {
+//!         // A real logger would also disable interrupts here
+//!         if TAKEN.swap(true, Ordering::Acquire) {
+//!             panic!("defmt logger taken reentrantly");
+//!         }
+//!         unsafe { (*addr_of_mut!(ENCODER)).start_frame(write) }
+//!     }
+//!
+//!     unsafe fn flush() {
+//!         (*addr_of_mut!(SERIAL)).flush();
+//!     }
+//!
+//!     unsafe fn release() {
+//!         (*addr_of_mut!(ENCODER)).end_frame(write);
+//!         TAKEN.store(false, Ordering::Release);
+//!     }
+//!
+//!     unsafe fn write(bytes: &[u8]) {
+//!         (*addr_of_mut!(ENCODER)).write(bytes, write);
+//!     }
+//! }
+//!
+//! fn write(bytes: &[u8]) {
+//!     unsafe { (*addr_of_mut!(SERIAL)).send_bytes(bytes) }
+//! }
+//! # }
+//! ```
 
 #![no_std]
 #![warn(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-use core::fmt;
+use core::{convert::TryFrom, fmt};
 
 use bitflags::bitflags;
 
 macro_rules! retry_until_ok {
-    ($cond:expr) => {
+    ($port:ident, $cond:expr) => {{
+        let mut iteration = 0u32;
         loop {
             if let Ok(ok) = $cond {
                 break ok;
             }
-            core::hint::spin_loop();
+            $port.spin_wait(iteration);
+            iteration = iteration.wrapping_add(1);
+        }
+    }};
+}
+
+/// Implements the methods that the port types had before the driver moved into the
+/// [`Uart16550`] trait, so that code calling them keeps compiling without importing it.
+macro_rules! impl_inherent_methods {
+    ($port:ty) => {
+        impl $port {
+            /// Initializes the UART, see [`Uart16550::init`](crate::Uart16550::init).
+            pub fn init(&mut self) {
+                $crate::Uart16550::init(self)
+            }
+
+            /// Sends a byte on the serial port, see
+            /// [`Uart16550::send`](crate::Uart16550::send).
+            pub fn send(&mut self, data: u8) {
+                $crate::Uart16550::send(self, data)
+            }
+
+            /// Sends a raw byte on the serial port, see
+            /// [`Uart16550::send_raw`](crate::Uart16550::send_raw).
+            pub fn send_raw(&mut self, data: u8) {
+                $crate::Uart16550::send_raw(self, data)
+            }
+
+            /// Tries to send a raw byte on the serial port, see
+            /// [`Uart16550::try_send_raw`](crate::Uart16550::try_send_raw).
+            pub fn try_send_raw(&mut self, data: u8) -> Result<(), $crate::WouldBlockError> {
+                $crate::Uart16550::try_send_raw(self, data)
+            }
+
+            /// Receives a byte on the serial port, see
+            /// [`Uart16550::receive`](crate::Uart16550::receive).
+            pub fn receive(&mut self) -> u8 {
+                $crate::Uart16550::receive(self)
+            }
+
+            /// Tries to receive a byte on the serial port, see
+            /// [`Uart16550::try_receive`](crate::Uart16550::try_receive).
+            pub fn try_receive(&mut self) -> Result<u8, $crate::WouldBlockError> {
+                $crate::Uart16550::try_receive(self)
+            }
         }
     };
 }
 
+/// Number of spin iterations to wait after programming the baud rate divisor.
+///
+/// Some hardware mangles the first transmitted byte if it is written before the baud rate
+/// generator has settled on the new divisor.
+const DEFAULT_BAUD_SETTLE_SPINS: u32 = 64;
+
+/// Frequency of the standard 1.8432 MHz crystal driving the baud rate generator.
+const DEFAULT_CLOCK_HZ: u32 = 1_843_200;
+
+/// Baud rate used by `init`.
+const DEFAULT_BAUD_RATE: u32 = 38_400;
+
+/// Computes the divisor latch value for the given input clock and baud rate.
+///
+/// The UART samples each bit 16 times, so the divisor is `clock_hz / (16 * baud)`, rounded
+/// to the nearest integer. Most clocks can not generate common baud rates exactly, so a
+/// deviation of up to 2% is accepted, which is well within the tolerance of a UART receiver.
+fn divisor_for(clock_hz: u32, baud: u32) -> Result<u16, ConfigError> {
+    let divisor = closest_divisor(clock_hz, baud);
+    if divisor == 0 {
+        return Err(ConfigError::UnsupportedBaudRate);
+    }
+    let divisor = u16::try_from(divisor).map_err(|_| ConfigError::UnsupportedBaudRate)?;
+    let actual = u64::from(clock_hz) / (u64::from(divisor) * 16);
+    if actual.abs_diff(u64::from(baud)) * 50 > u64::from(baud) {
+        return Err(ConfigError::InexactBaudRate);
+    }
+    Ok(divisor)
+}
+
+/// Computes the divisor that comes closest to the given baud rate, without checking that it
+/// fits into the divisor latch.
+fn closest_divisor(clock_hz: u32, baud: u32) -> u64 {
+    let rate = u64::from(baud) * 16;
+    let clock_hz = u64::from(clock_hz);
+    match clock_hz.checked_div(rate) {
+        Some(_) => (clock_hz + rate / 2) / rate,
+        None => 0,
+    }
+}
+
+/// Byte sent during the loopback test.
+const LOOPBACK_TEST_BYTE: u8 = 0xAE;
+
+/// Number of spin iterations to wait for the byte sent during the loopback test.
+const LOOPBACK_TEST_SPINS: u32 = 100_000;
+
+/// Spins for the given number of iterations.
+fn spin_for(iterations: u32) {
+    for _ in 0..iterations {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(feature = "async")]
+/// Async wrapper implementation
+mod asynch;
+/// Line configuration
+mod config;
+/// Register access and state shared by all UART implementations
+mod driver;
+#[cfg(feature = "genio")]
+/// `genio` trait implementations
+mod generic_io;
+#[cfg(feature = "embedded-hal")]
+/// `embedded-hal` trait implementations
+mod hal;
+#[cfg(feature = "embedded-io")]
+/// `embedded-io` trait implementations
+mod io;
 /// Memory mapped implementation
 mod mmio;
+/// No-op implementation for builds without a UART
+mod null;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 /// Port asm commands implementation
 mod port;
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+/// Placeholder pointing users of port-mapped I/O to the memory-mapped implementation
+mod port_unavailable;
+/// Implementation on top of user supplied register accesses
+mod registers;
+#[cfg(test)]
+/// Unit tests on a simulated UART
+mod tests;
+/// Driver shared by all UART implementations
+mod uart;
+#[cfg(feature = "ufmt")]
+/// `ufmt` trait implementations
+mod uwrite;
 
-pub use crate::mmio::MmioSerialPort;
+#[cfg(feature = "async")]
+pub use crate::asynch::{AsyncSerialPort, SerialWaker};
+pub use crate::config::{DataBits, FifoTrigger, FlowControl, LineConfig, Parity, StopBits};
+#[cfg(feature = "embedded-io")]
+pub use crate::io::BufferedSerialPort;
+pub use crate::mmio::{MmioSerialPort, RegisterWidth};
+pub use crate::null::NullSerialPort;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 pub use crate::port::SerialPort;
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+#[doc(hidden)]
+#[allow(deprecated)]
+pub use crate::port_unavailable::SerialPort;
+pub use crate::registers::{GenericSerialPort, RegisterAccess};
+pub use crate::uart::Uart16550;
 
 bitflags! {
     /// Interrupt enable flags
     #[repr(transparent)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    struct IntEnFlags: u8 {
+    pub struct IntEnFlags: u8 {
+        /// Received data available.
         const RECEIVED = 1;
+        /// Transmitter holding register empty.
         const SENT = 1 << 1;
+        /// Receiver line status changed, e.g. because of an error.
         const ERRORED = 1 << 2;
+        /// Modem status changed.
         const STATUS_CHANGE = 1 << 3;
         // 4 to 7 are unused
     }
@@ -102,19 +316,281 @@ bitflags! {
 
 bitflags! {
     /// Line status flags
+    ///
+    /// Bits 0 to 6 behave the same on all 8250 compatible chips. Bit 7 is only meaningful
+    /// on chips with a working FIFO, i.e. the 16550A and later, and reads as zero otherwise.
     #[repr(transparent)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    struct LineStsFlags: u8 {
+    pub struct LineStsFlags: u8 {
+        /// Received data is available.
         const INPUT_FULL = 1;
-        // 1 to 4 unknown
+        /// Received data was lost because the receive buffer was full.
+        const OVERRUN_ERROR = 1 << 1;
+        /// The received byte has a wrong parity bit.
+        const PARITY_ERROR = 1 << 2;
+        /// The received byte has no valid stop bit.
+        const FRAMING_ERROR = 1 << 3;
+        /// A break condition was detected on the line.
+        const BREAK_INTERRUPT = 1 << 4;
+        /// The transmitter holding register is empty, so a new byte can be sent.
         const OUTPUT_EMPTY = 1 << 5;
-        // 6 and 7 unknown
+        /// The transmitter holding and shift registers are empty, so all data was sent.
+        const TRANSMITTER_EMPTY = 1 << 6;
+        /// At least one byte in the receive FIFO has an error (16550A and later).
+        const FIFO_ERROR = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// Modem control flags
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ModemCtrlFlags: u8 {
+        /// Data terminal ready.
+        const DTR = 1;
+        /// Request to send.
+        const RTS = 1 << 1;
+        /// Auxiliary output #1.
+        const OUT1 = 1 << 2;
+        /// Auxiliary output #2, used as interrupt line for the CPU.
+        const OUT2 = 1 << 3;
+        /// Loopback mode.
+        const LOOPBACK = 1 << 4;
+        /// Automatic flow control (16750 only).
+        const AUTO_FLOW_CONTROL = 1 << 5;
+        // 6 and 7 are unused
+    }
+}
+
+bitflags! {
+    /// Modem status flags
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ModemStsFlags: u8 {
+        /// `CTS` changed since the last read.
+        const DELTA_CTS = 1;
+        /// `DSR` changed since the last read.
+        const DELTA_DSR = 1 << 1;
+        /// `RING` changed from active to inactive since the last read.
+        const TRAILING_EDGE_RING = 1 << 2;
+        /// `CARRIER_DETECT` changed since the last read.
+        const DELTA_CARRIER_DETECT = 1 << 3;
+        /// Clear to send.
+        const CTS = 1 << 4;
+        /// Data set ready.
+        const DSR = 1 << 5;
+        /// Ring indicator.
+        const RING = 1 << 6;
+        /// Data carrier detect.
+        const CARRIER_DETECT = 1 << 7;
+    }
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! impl_defmt_format_for_flags {
+    ($($flags:ident),*) => {
+        $(
+            #[cfg_attr(docsrs, doc(cfg(feature = "defmt")))]
+            impl defmt::Format for $flags {
+                fn format(&self, f: defmt::Formatter<'_>) {
+                    defmt::write!(f, "{}({=u8:#010b})", stringify!($flags), self.bits())
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "defmt")]
+impl_defmt_format_for_flags!(IntEnFlags, LineStsFlags, ModemCtrlFlags, ModemStsFlags);
+
+/// The UART chip variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UartVariant {
+    /// The original 8250, without FIFO and scratch register.
+    Uart8250,
+    /// A 16450, without FIFO.
+    Uart16450,
+    /// A 16550, whose FIFO is present but does not work reliably.
+    Uart16550,
+    /// A 16550A, with a working 16 byte FIFO.
+    Uart16550A,
+    /// A 16750, with a 64 byte FIFO.
+    Uart16750,
+}
+
+/// Cause of a UART interrupt, as reported by the interrupt identification register.
+///
+/// The variants are listed by decreasing priority. Only the pending interrupt with the
+/// highest priority is reported at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InterruptCause {
+    /// No interrupt is pending.
+    None,
+    /// A receive error or break occurred. Cleared by reading the line status.
+    ReceiverLineStatus,
+    /// Received data is available. Cleared by reading the data until the FIFO is below the
+    /// trigger level.
+    ReceivedData,
+    /// Data in the receive FIFO has not been read for a while. Cleared by reading the data.
+    CharacterTimeout,
+    /// The transmitter holding register is empty. Cleared by reading the interrupt
+    /// identification register or by sending data.
+    TransmitterEmpty,
+    /// The modem status changed. Cleared by reading the modem status.
+    ModemStatus,
+}
+
+/// State of the FIFOs as reported by the interrupt identification register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FifoStatus {
+    /// The FIFOs are enabled and working, as on a 16550A or later.
+    Enabled,
+    /// The FIFOs are enabled but unusable, as on the original 16550.
+    Unusable,
+    /// The FIFOs are disabled or the chip has none.
+    Disabled,
+}
+
+/// Contents of the interrupt identification register.
+///
+/// A single read of this register reports both the pending interrupt and the state of the
+/// FIFOs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Iir(u8);
+
+impl Iir {
+    /// Returns the raw register value.
+    pub const fn raw(self) -> u8 {
+        self.0
+    }
+
+    /// Whether an interrupt is pending.
+    ///
+    /// Bit 0 of the register is active low.
+    pub const fn interrupt_pending(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    /// The interrupt identification code stored in bits 1 to 3.
+    pub const fn interrupt_id(self) -> u8 {
+        (self.0 >> 1) & 0b111
+    }
+
+    /// Decodes the cause of the pending interrupt with the highest priority.
+    pub const fn cause(self) -> InterruptCause {
+        match self.0 & 0b1111 {
+            0b0110 => InterruptCause::ReceiverLineStatus,
+            0b0100 => InterruptCause::ReceivedData,
+            0b1100 => InterruptCause::CharacterTimeout,
+            0b0010 => InterruptCause::TransmitterEmpty,
+            0b0000 => InterruptCause::ModemStatus,
+            _ => InterruptCause::None,
+        }
+    }
+
+    /// Whether the 64 byte FIFO is enabled (bit 5, 16750 only).
+    pub const fn fifo_64_byte_enabled(self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// Determines the chip variant from a read-back after enabling the FIFO.
+    ///
+    /// Returns `None` if the chip has no FIFO.
+    const fn fifo_variant(self) -> Option<UartVariant> {
+        match self.fifo_enabled_bits() {
+            0b11 if self.fifo_64_byte_enabled() => Some(UartVariant::Uart16750),
+            0b11 => Some(UartVariant::Uart16550A),
+            0b10 => Some(UartVariant::Uart16550),
+            _ => None,
+        }
+    }
+
+    /// The FIFO enabled status stored in bits 6 and 7.
+    ///
+    /// This is `0b11` for a working FIFO, `0b10` for a FIFO that is present but unusable
+    /// and `0b00` if there is no FIFO.
+    pub const fn fifo_enabled_bits(self) -> u8 {
+        self.0 >> 6
+    }
+
+    /// Decodes the FIFO enabled status stored in bits 6 and 7.
+    pub const fn fifo_status(self) -> FifoStatus {
+        match self.fifo_enabled_bits() {
+            0b11 => FifoStatus::Enabled,
+            0b10 => FifoStatus::Unusable,
+            _ => FifoStatus::Disabled,
+        }
+    }
+}
+
+/// Modem line transitions reported by a single read of the modem status register.
+///
+/// The delta bits tell which lines changed since the last read, which is what raises the
+/// modem status interrupt enabled through [`IntEnFlags::STATUS_CHANGE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ModemStatusEvent(ModemStsFlags);
+
+impl ModemStatusEvent {
+    /// Returns the raw modem status.
+    pub const fn status(self) -> ModemStsFlags {
+        self.0
+    }
+
+    /// Whether any of the lines changed.
+    pub const fn any_changed(self) -> bool {
+        self.0.bits() & 0x0F != 0
+    }
+
+    /// Whether the clear to send line changed.
+    pub const fn cts_changed(self) -> bool {
+        self.0.contains(ModemStsFlags::DELTA_CTS)
+    }
+
+    /// Whether the data set ready line changed.
+    pub const fn dsr_changed(self) -> bool {
+        self.0.contains(ModemStsFlags::DELTA_DSR)
+    }
+
+    /// Whether the ring indicator line went inactive.
+    pub const fn ring_ended(self) -> bool {
+        self.0.contains(ModemStsFlags::TRAILING_EDGE_RING)
+    }
+
+    /// Whether the data carrier detect line changed.
+    pub const fn carrier_detect_changed(self) -> bool {
+        self.0.contains(ModemStsFlags::DELTA_CARRIER_DETECT)
+    }
+
+    /// Whether the clear to send line is active.
+    pub const fn cts(self) -> bool {
+        self.0.contains(ModemStsFlags::CTS)
+    }
+
+    /// Whether the data set ready line is active.
+    pub const fn dsr(self) -> bool {
+        self.0.contains(ModemStsFlags::DSR)
+    }
+
+    /// Whether the ring indicator line is active.
+    pub const fn ring(self) -> bool {
+        self.0.contains(ModemStsFlags::RING)
+    }
+
+    /// Whether the data carrier detect line is active.
+    pub const fn carrier_detect(self) -> bool {
+        self.0.contains(ModemStsFlags::CARRIER_DETECT)
     }
 }
 
 /// The `WouldBlockError` error indicates that the serial device was not ready immediately.
 #[non_exhaustive]
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct WouldBlockError;
 
 impl fmt::Display for WouldBlockError {
@@ -122,3 +598,363 @@ impl fmt::Display for WouldBlockError {
         f.write_str("serial device not ready")
     }
 }
+
+#[rustversion::since(1.81)]
+impl core::error::Error for WouldBlockError {}
+
+/// The `ConfigError` error indicates that the requested configuration is not supported.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The baud rate is zero or out of the range supported by the divisor latch.
+    UnsupportedBaudRate,
+    /// The baud rate can not be generated from the input clock with sufficient accuracy.
+    InexactBaudRate,
+    /// The number of stop bits is not available with the configured word length.
+    UnsupportedStopBits,
+    /// The feature is not supported by the UART chip variant.
+    UnsupportedVariant,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedBaudRate => f.write_str("unsupported baud rate"),
+            Self::InexactBaudRate => f.write_str("baud rate can not be generated accurately"),
+            Self::UnsupportedStopBits => {
+                f.write_str("number of stop bits is not supported with this word length")
+            }
+            Self::UnsupportedVariant => f.write_str("not supported by this UART variant"),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for ConfigError {}
+
+/// The `ReceiveError` error indicates that no valid byte could be received.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReceiveError {
+    /// No data is available yet.
+    WouldBlock,
+    /// Received data was lost because the receive buffer was full.
+    Overrun,
+    /// The received byte has a wrong parity bit.
+    Parity,
+    /// The received byte has no valid stop bit.
+    Framing,
+    /// A break condition was detected on the line.
+    Break,
+}
+
+impl From<WouldBlockError> for ReceiveError {
+    fn from(_: WouldBlockError) -> Self {
+        Self::WouldBlock
+    }
+}
+
+impl fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => f.write_str("serial device not ready"),
+            Self::Overrun => f.write_str("receive overrun"),
+            Self::Parity => f.write_str("parity error"),
+            Self::Framing => f.write_str("framing error"),
+            Self::Break => f.write_str("break condition"),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for ReceiveError {}
+
+/// Outcome of a bulk read that also reports lost data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadResult {
+    /// The number of bytes that were read.
+    pub count: usize,
+    /// Whether received data was lost because the receive buffer was full.
+    pub overran: bool,
+}
+
+/// Iterator that receives bytes, waiting until each of them is available.
+///
+/// Returned by [`Uart16550::bytes`]. The iterator never ends.
+#[derive(Debug)]
+pub struct Bytes<'a, U: ?Sized>(&'a mut U);
+
+impl<'a, U: Uart16550 + ?Sized> Bytes<'a, U> {
+    pub(crate) fn new(port: &'a mut U) -> Self {
+        Self(port)
+    }
+}
+
+impl<U: Uart16550 + ?Sized> Iterator for Bytes<'_, U> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        Some(self.0.receive())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+/// Iterator over the bytes that are already available.
+///
+/// Returned by [`Uart16550::try_bytes`]. The iterator ends as soon as no more data is
+/// available, but yields bytes again once new data arrived.
+#[derive(Debug)]
+pub struct TryBytes<'a, U: ?Sized>(&'a mut U);
+
+impl<'a, U: Uart16550 + ?Sized> TryBytes<'a, U> {
+    pub(crate) fn new(port: &'a mut U) -> Self {
+        Self(port)
+    }
+}
+
+impl<U: Uart16550 + ?Sized> Iterator for TryBytes<'_, U> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        self.0.try_receive().ok()
+    }
+}
+
+/// The `SendError` error indicates that a byte was not sent.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SendError {
+    /// The transmitter is not ready yet.
+    WouldBlock,
+    /// A break condition was detected on the line, which may be shorted or miswired.
+    Break,
+    /// The line status reads as all ones, so there is probably no UART at this address.
+    NoDevice,
+}
+
+impl From<WouldBlockError> for SendError {
+    fn from(_: WouldBlockError) -> Self {
+        Self::WouldBlock
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => f.write_str("serial device not ready"),
+            Self::Break => f.write_str("break condition"),
+            Self::NoDevice => f.write_str("no serial device present"),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for SendError {}
+
+/// The `IoError` error is returned by the I/O trait implementations when data can not be
+/// transferred at all.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IoError {
+    /// The transmitter was disabled through `set_tx_enabled`, so no data can be written.
+    TxDisabled,
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TxDisabled => f.write_str("transmitter disabled"),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for IoError {}
+
+/// The `LoopbackTestError` error indicates that a UART failed its loopback test.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LoopbackTestError {
+    /// The byte could not be sent or was not received in time.
+    Timeout {
+        /// The byte that was sent.
+        expected: u8,
+    },
+    /// A different byte than the sent one was received.
+    Mismatch {
+        /// The byte that was sent.
+        expected: u8,
+        /// The byte that was received.
+        received: u8,
+    },
+}
+
+impl fmt::Display for LoopbackTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout { expected } => {
+                write!(f, "loopback test timed out waiting for {:#04x}", expected)
+            }
+            Self::Mismatch { expected, received } => write!(
+                f,
+                "loopback test received {:#04x} instead of {:#04x}",
+                received, expected
+            ),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for LoopbackTestError {}
+
+/// Bit patterns sent by `loopback_diagnose`, which drive every data bit both high and low.
+const LOOPBACK_DIAGNOSE_PATTERNS: [u8; 4] = [0x00, 0xFF, 0xAA, 0x55];
+
+/// Result of a loopback diagnosis, identifying data bits that do not follow the sent data.
+///
+/// Each bit of the masks corresponds to the data bit at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LoopbackDiagnosis {
+    stuck_high: u8,
+    stuck_low: u8,
+    timed_out: bool,
+}
+
+impl LoopbackDiagnosis {
+    /// Creates a diagnosis without any faults.
+    pub(crate) const fn new() -> Self {
+        Self {
+            stuck_high: 0,
+            stuck_low: 0,
+            timed_out: false,
+        }
+    }
+
+    /// Records the byte that was received for the sent one.
+    pub(crate) fn record(&mut self, sent: u8, received: u8) {
+        self.stuck_high |= !sent & received;
+        self.stuck_low |= sent & !received;
+    }
+
+    /// Records that a byte could not be sent or was not received in time.
+    pub(crate) fn record_timeout(&mut self) {
+        self.timed_out = true;
+    }
+
+    /// Whether all sent bytes were received unchanged.
+    pub const fn is_ok(self) -> bool {
+        self.faulty_bits() == 0 && !self.timed_out
+    }
+
+    /// Returns the mask of data bits that were received as 1 although 0 was sent.
+    pub const fn stuck_high(self) -> u8 {
+        self.stuck_high
+    }
+
+    /// Returns the mask of data bits that were received as 0 although 1 was sent.
+    pub const fn stuck_low(self) -> u8 {
+        self.stuck_low
+    }
+
+    /// Returns the mask of all data bits that did not follow the sent data.
+    pub const fn faulty_bits(self) -> u8 {
+        self.stuck_high | self.stuck_low
+    }
+
+    /// Whether any of the patterns could not be sent or was not received at all.
+    pub const fn timed_out(self) -> bool {
+        self.timed_out
+    }
+}
+
+/// Copy of the configuration registers of a UART, to quickly reapply a known-good
+/// configuration, e.g. after a reset.
+///
+/// A snapshot is taken with `capture` and applied with `restore` of the port types. It is
+/// built from the values last written through the port, as most of these registers can not
+/// be read back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterSnapshot {
+    pub(crate) divisor: u16,
+    pub(crate) baud: u32,
+    pub(crate) line_ctrl: u8,
+    pub(crate) int_en: IntEnFlags,
+    pub(crate) fifo_ctrl: u8,
+    pub(crate) modem_ctrl: ModemCtrlFlags,
+}
+
+/// The `InitError` error indicates that a UART could not be set up.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InitError {
+    /// The line configuration is not supported.
+    Config(ConfigError),
+    /// The UART failed its loopback test.
+    Loopback(LoopbackTestError),
+}
+
+impl From<ConfigError> for InitError {
+    fn from(err: ConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<LoopbackTestError> for InitError {
+    fn from(err: LoopbackTestError) -> Self {
+        Self::Loopback(err)
+    }
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(err) => fmt::Display::fmt(err, f),
+            Self::Loopback(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for InitError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Config(err) => Some(err),
+            Self::Loopback(err) => Some(err),
+        }
+    }
+}
+
+/// The `MmioError` error indicates that a memory-mapped register address is invalid.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MmioError {
+    /// The base address is zero.
+    NullAddress,
+    /// The base address or the stride is not aligned to the register width.
+    Misaligned,
+}
+
+impl fmt::Display for MmioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NullAddress => f.write_str("register base address is null"),
+            Self::Misaligned => f.write_str("register address is not aligned to its width"),
+        }
+    }
+}
+
+#[rustversion::since(1.81)]
+impl core::error::Error for MmioError {}
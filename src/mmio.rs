@@ -1,24 +1,58 @@
 use core::{
     fmt,
-    sync::atomic::{AtomicPtr, Ordering},
+    ptr::NonNull,
+    sync::atomic::{self, AtomicPtr, Ordering},
 };
 
-use crate::{LineStsFlags, WouldBlockError};
+use crate::{
+    driver::{Driver, Register, State},
+    uart, InitError, LineConfig, LoopbackTestError, MmioError, Uart16550,
+};
+
+/// Width of the register accesses of a [`MmioSerialPort`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterWidth {
+    /// Byte-sized registers.
+    U8,
+    /// 16 bit registers, of which only the low byte is used.
+    U16,
+    /// 32 bit registers, of which only the low byte is used.
+    U32,
+    /// Big-endian 32 bit registers, of which only the low byte is used.
+    U32Be,
+}
+
+impl RegisterWidth {
+    /// Size of a register access in bytes.
+    const fn size(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 | Self::U32Be => 4,
+        }
+    }
+}
 
 /// A memory-mapped UART.
+///
+/// The port only stores the base address and layout of its registers and accesses them
+/// through volatile raw pointer operations. It has no lifetime parameter, so it can be kept
+/// in a global, e.g. for a boot-time logger, see [`const_new`](Self::const_new).
 #[derive(Debug)]
 pub struct MmioSerialPort {
-    data: AtomicPtr<u8>,
-    int_en: AtomicPtr<u8>,
-    fifo_ctrl: AtomicPtr<u8>,
-    line_ctrl: AtomicPtr<u8>,
-    modem_ctrl: AtomicPtr<u8>,
-    line_sts: AtomicPtr<u8>,
+    base: AtomicPtr<u8>,
+    stride: usize,
+    width: RegisterWidth,
+    fenced: bool,
+    state: State,
 }
 
 impl MmioSerialPort {
     /// Creates a new UART interface on the given memory mapped address.
     ///
+    /// # Safety
+    ///
     /// This function is unsafe because the caller must ensure that the given base address
     /// really points to a serial port device.
     #[rustversion::attr(since(1.61), const)]
@@ -29,115 +63,563 @@ impl MmioSerialPort {
     /// Creates a new UART interface on the given memory mapped address with a given
     /// register stride.
     ///
+    /// Register `n` is accessed at `base + n * stride`, e.g. the line control register at
+    /// `base + 12` for a stride of 4:
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new_with_stride(0x1000_0000, 4) };
+    /// serial_port.init();
+    /// serial_port.send(b'a');
+    /// ```
+    ///
+    /// # Safety
+    ///
     /// This function is unsafe because the caller must ensure that the given base address
     /// really points to a serial port device.
     #[rustversion::attr(since(1.61), const)]
     pub unsafe fn new_with_stride(base: usize, stride: usize) -> Self {
-        let base_pointer = base as *mut u8;
-        Self {
-            data: AtomicPtr::new(base_pointer),
-            int_en: AtomicPtr::new(base_pointer.add(1 * stride)),
-            fifo_ctrl: AtomicPtr::new(base_pointer.add(2 * stride)),
-            line_ctrl: AtomicPtr::new(base_pointer.add(3 * stride)),
-            modem_ctrl: AtomicPtr::new(base_pointer.add(4 * stride)),
-            line_sts: AtomicPtr::new(base_pointer.add(5 * stride)),
-        }
+        Self::from_ptr(base as *mut u8, stride, RegisterWidth::U8)
     }
 
-    /// Initializes the memory-mapped UART.
+    /// Creates a new UART interface on the given memory mapped address with a register
+    /// stride of `1 << shift`.
     ///
-    /// The default configuration of [38400/8-N-1](https://en.wikipedia.org/wiki/8-N-1) is used.
-    pub fn init(&mut self) {
-        let self_int_en = self.int_en.load(Ordering::Relaxed);
-        let self_line_ctrl = self.line_ctrl.load(Ordering::Relaxed);
-        let self_data = self.data.load(Ordering::Relaxed);
-        let self_fifo_ctrl = self.fifo_ctrl.load(Ordering::Relaxed);
-        let self_modem_ctrl = self.modem_ctrl.load(Ordering::Relaxed);
-        unsafe {
-            // Disable interrupts
-            self_int_en.write(0x00);
+    /// This matches the `reg-shift` property of device tree nodes for 16550 compatible UARTs.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::from_reg_shift(0x1000_0000, 2) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    #[rustversion::attr(since(1.61), const)]
+    pub unsafe fn from_reg_shift(base: usize, shift: u32) -> Self {
+        Self::new_with_stride(base, 1 << shift)
+    }
 
-            // Enable DLAB
-            self_line_ctrl.write(0x80);
+    /// Creates a new UART interface on the given memory mapped address with 16 bit wide
+    /// registers and a given register stride.
+    ///
+    /// Each register is accessed as a whole 16 bit word, of which only the low byte is
+    /// meaningful.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new_16bit(0x1000_0000, 4) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the base address and stride are
+    /// suitably aligned for 16 bit accesses.
+    #[rustversion::attr(since(1.61), const)]
+    pub unsafe fn new_16bit(base: usize, stride: usize) -> Self {
+        Self::from_ptr(base as *mut u8, stride, RegisterWidth::U16)
+    }
+
+    /// Creates a new UART interface on the given memory mapped address with 32 bit wide
+    /// registers and a given register stride.
+    ///
+    /// Each register is accessed as a whole 32 bit word, of which only the low byte is
+    /// meaningful. This layout is common on ARM and RISC-V SoCs, where the stride is usually
+    /// 4 bytes.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new_32bit(0x1000_0000, 4) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the base address and stride are
+    /// suitably aligned for 32 bit accesses.
+    #[rustversion::attr(since(1.61), const)]
+    pub unsafe fn new_32bit(base: usize, stride: usize) -> Self {
+        Self::from_ptr(base as *mut u8, stride, RegisterWidth::U32)
+    }
 
-            // Set maximum speed to 38400 bps by configuring DLL and DLM
-            self_data.write(0x03);
-            self_int_en.write(0x00);
+    /// Creates a new UART interface on the given memory mapped address with big-endian 32
+    /// bit wide registers and a given register stride.
+    ///
+    /// Like [`new_32bit`](Self::new_32bit), but the registers are accessed as big-endian
+    /// words, so the meaningful byte is the one at the highest address of each register.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new_32bit_be(0x1000_0000, 4) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the base address and stride are
+    /// suitably aligned for 32 bit accesses.
+    #[rustversion::attr(since(1.61), const)]
+    pub unsafe fn new_32bit_be(base: usize, stride: usize) -> Self {
+        Self::from_ptr(base as *mut u8, stride, RegisterWidth::U32Be)
+    }
 
-            // Disable DLAB and set data word length to 8 bits
-            self_line_ctrl.write(0x03);
+    /// Creates a new UART interface on the given memory mapped address with a given
+    /// register stride.
+    ///
+    /// Unlike [`new`](Self::new) and [`new_with_stride`](Self::new_with_stride), this function
+    /// is `const` on all supported Rust versions, so it can always be used to initialize a
+    /// `static`:
+    ///
+    /// ```no_run
+    /// use core::ptr::NonNull;
+    /// use uart_16550::MmioSerialPort;
+    ///
+    /// static SERIAL: MmioSerialPort =
+    ///     unsafe { MmioSerialPort::const_new(NonNull::new_unchecked(0x1000_0000 as *mut u8), 1) };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    pub const unsafe fn const_new(base: NonNull<u8>, stride: usize) -> Self {
+        Self::from_ptr(base.as_ptr(), stride, RegisterWidth::U8)
+    }
 
-            // Enable FIFO, clear TX/RX queues and
-            // set interrupt watermark at 14 bytes
-            self_fifo_ctrl.write(0xC7);
+    /// Recreates a UART interface from the parts returned by
+    /// [`into_raw_parts`](Self::into_raw_parts).
+    ///
+    /// Only the register layout is restored. Everything else starts out in the same state as
+    /// for a newly created port:
+    ///
+    /// - the copies of the write-only line control, interrupt enable, modem control and FIFO
+    ///   control registers are empty, as are the cached baud rate and divisor,
+    /// - the clock frequency is the default 1.8432 MHz,
+    /// - the relax hook and interval, see [`set_relax`](Self::set_relax), are reset,
+    /// - fences, see [`set_fenced`](Self::set_fenced), are disabled,
+    /// - transmission is enabled, newline translation is on and flow control is off,
+    /// - the detected [`UartVariant`](crate::UartVariant) is forgotten,
+    /// - overrun resets and received data interrupt masking are off and the baud settle
+    ///   delay is the default.
+    ///
+    /// So the configuration reported by the port only matches the hardware after it has been
+    /// configured again, e.g. through [`init_preserve_baud`](Self::init_preserve_baud).
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the base address and stride are
+    /// suitably aligned for the register width.
+    #[rustversion::attr(since(1.61), const)]
+    pub unsafe fn from_raw_parts(base: *mut u8, stride: usize, width: RegisterWidth) -> Self {
+        Self::from_ptr(base, stride, width)
+    }
 
-            // Mark data terminal ready, signal request to send
-            // and enable auxilliary output #2 (used as interrupt line for CPU)
-            self_modem_ctrl.write(0x0B);
+    /// Creates a new UART interface on the given memory mapped address with a given
+    /// register stride, after checking the address.
+    ///
+    /// Like [`new_with_stride`](Self::new_with_stride), but returns an error instead of
+    /// creating the port if the base address is null.
+    ///
+    /// ```
+    /// use uart_16550::{MmioError, MmioSerialPort};
+    ///
+    /// assert_eq!(
+    ///     unsafe { MmioSerialPort::try_new(0, 1) }.unwrap_err(),
+    ///     MmioError::NullAddress
+    /// );
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    pub unsafe fn try_new(base: usize, stride: usize) -> Result<Self, MmioError> {
+        Self::checked(base, stride, RegisterWidth::U8)
+    }
 
-            // Enable interrupts
-            self_int_en.write(0x01);
+    /// Creates a new UART interface on the given memory mapped address with 32 bit wide
+    /// registers and a given register stride, after checking the address.
+    ///
+    /// Like [`new_32bit`](Self::new_32bit), but returns an error instead of creating the
+    /// port if the base address is null or the base address or stride are not a multiple
+    /// of 4.
+    ///
+    /// ```
+    /// use uart_16550::{MmioError, MmioSerialPort};
+    ///
+    /// assert_eq!(
+    ///     unsafe { MmioSerialPort::try_new_32bit(0x1000_0002, 4) }.unwrap_err(),
+    ///     MmioError::Misaligned
+    /// );
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    pub unsafe fn try_new_32bit(base: usize, stride: usize) -> Result<Self, MmioError> {
+        Self::checked(base, stride, RegisterWidth::U32)
+    }
+
+    /// Checks the register layout before creating the port.
+    fn checked(base: usize, stride: usize, width: RegisterWidth) -> Result<Self, MmioError> {
+        let align = width.size();
+        if base == 0 {
+            Err(MmioError::NullAddress)
+        } else if (base | stride) & (align - 1) != 0 {
+            Err(MmioError::Misaligned)
+        } else {
+            Ok(Self::from_ptr(base as *mut u8, stride, width))
         }
     }
 
-    fn line_sts(&mut self) -> LineStsFlags {
-        unsafe { LineStsFlags::from_bits_truncate(*self.line_sts.load(Ordering::Relaxed)) }
+    const fn from_ptr(base: *mut u8, stride: usize, width: RegisterWidth) -> Self {
+        Self {
+            base: AtomicPtr::new(base),
+            stride,
+            width,
+            fenced: false,
+            state: State::new(),
+        }
     }
 
-    /// Sends a byte on the serial port.
-    pub fn send(&mut self, data: u8) {
-        match data {
-            8 | 0x7F => {
-                self.send_raw(8);
-                self.send_raw(b' ');
-                self.send_raw(8);
-            }
-            data => {
-                self.send_raw(data);
-            }
+    /// Returns the base address of the UART, i.e. the address of the data register.
+    ///
+    /// ```
+    /// use uart_16550::MmioSerialPort;
+    ///
+    /// let serial_port = unsafe { MmioSerialPort::new_32bit(0x1000_0000, 4) };
+    /// assert_eq!(serial_port.base_address(), 0x1000_0000);
+    /// assert_eq!(serial_port.stride(), 4);
+    /// ```
+    pub fn base_address(&self) -> usize {
+        self.base.load(Ordering::Relaxed) as usize
+    }
+
+    /// Returns the distance between the addresses of two consecutive registers in bytes.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the width of the register accesses.
+    pub fn register_width(&self) -> RegisterWidth {
+        self.width
+    }
+
+    /// Consumes the port and returns its base address, register stride and register width.
+    ///
+    /// The parts can be passed across an FFI boundary, e.g. to a later boot stage, and turned
+    /// back into a port with [`from_raw_parts`](Self::from_raw_parts). Only the register
+    /// layout survives the round trip, see `from_raw_parts` for the state that is lost.
+    ///
+    /// ```
+    /// use uart_16550::{MmioSerialPort, RegisterWidth};
+    ///
+    /// let serial_port = unsafe { MmioSerialPort::new_32bit(0x1000_0000, 4) };
+    /// let (ptr, stride, width) = serial_port.into_raw_parts();
+    /// let serial_port = unsafe { MmioSerialPort::from_raw_parts(ptr, stride, width) };
+    /// assert_eq!(serial_port.base_address(), 0x1000_0000);
+    /// assert_eq!(serial_port.stride(), 4);
+    /// assert_eq!(serial_port.register_width(), RegisterWidth::U32);
+    /// ```
+    pub fn into_raw_parts(self) -> (*mut u8, usize, RegisterWidth) {
+        (self.base.load(Ordering::Relaxed), self.stride, self.width)
+    }
+
+    /// Address of the register at the given offset.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn reg(&self, offset: usize) -> *mut u8 {
+        self.base
+            .load(Ordering::Relaxed)
+            .wrapping_add(offset.wrapping_mul(self.stride))
+    }
+
+    /// Orders the surrounding register accesses, if enabled.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn fence(&self) {
+        if self.fenced {
+            atomic::fence(Ordering::SeqCst);
         }
     }
 
-    /// Sends a raw byte on the serial port, intended for binary data.
-    pub fn send_raw(&mut self, data: u8) {
-        retry_until_ok!(self.try_send_raw(data))
+    /// Creates and initializes a new UART interface on the given base address and checks
+    /// that it works using [`loopback_test`](Self::loopback_test).
+    ///
+    /// ```no_run
+    /// use uart_16550::MmioSerialPort;
+    ///
+    /// match unsafe { MmioSerialPort::try_create(0x1000_0000) } {
+    ///     Ok(serial_port) => {
+    ///         // use the port
+    ///     }
+    ///     Err(err) => {
+    ///         // no working UART at this address
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    pub unsafe fn try_create(base: usize) -> Result<Self, LoopbackTestError> {
+        let mut serial_port = Self::new(base);
+        serial_port.init();
+        serial_port.loopback_test()?;
+        Ok(serial_port)
     }
 
-    /// Tries to send a raw byte on the serial port, intended for binary data.
-    pub fn try_send_raw(&mut self, data: u8) -> Result<(), WouldBlockError> {
-        if self.line_sts().contains(LineStsFlags::OUTPUT_EMPTY) {
-            let self_data = self.data.load(Ordering::Relaxed);
-            unsafe {
-                self_data.write(data);
+    /// Creates a new UART interface on the given base address, initializes it with the
+    /// given line configuration and checks that it works using
+    /// [`loopback_test`](Self::loopback_test).
+    ///
+    /// ```no_run
+    /// use uart_16550::{LineConfig, MmioSerialPort};
+    ///
+    /// let config = LineConfig::new().baud(115200);
+    /// let serial_port = unsafe { MmioSerialPort::open(0x1000_0000, config) }.unwrap();
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device.
+    pub unsafe fn open(base: usize, config: LineConfig) -> Result<Self, InitError> {
+        let mut serial_port = Self::new(base);
+        serial_port.init();
+        serial_port.configure(config)?;
+        serial_port.loopback_test()?;
+        Ok(serial_port)
+    }
+
+    /// Enables or disables memory fences around every register access.
+    ///
+    /// Volatile accesses are never reordered by the compiler, but some platforms map the UART
+    /// in a way that lets the CPU reorder them, e.g. a write of the data register with a
+    /// subsequent read of the line status. When enabled, a sequentially consistent
+    /// [`fence`](core::sync::atomic::fence) is issued before and after each access. This is
+    /// disabled by default.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.set_fenced(true);
+    /// serial_port.init();
+    /// serial_port.send_raw(b'a');
+    /// ```
+    pub fn set_fenced(&mut self, enabled: bool) {
+        self.fenced = enabled;
+    }
+}
+
+impl Driver for MmioSerialPort {
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn read(&mut self, reg: Register) -> u8 {
+        let reg = self.reg(reg.0.into());
+        self.fence();
+        let value = unsafe {
+            match self.width {
+                RegisterWidth::U8 => reg.read_volatile(),
+                RegisterWidth::U16 => reg.cast::<u16>().read_volatile() as u8,
+                RegisterWidth::U32 => reg.cast::<u32>().read_volatile() as u8,
+                RegisterWidth::U32Be => u32::from_be(reg.cast::<u32>().read_volatile()) as u8,
+            }
+        };
+        self.fence();
+        value
+    }
+
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn write(&mut self, reg: Register, value: u8) {
+        let reg = self.reg(reg.0.into());
+        self.fence();
+        unsafe {
+            match self.width {
+                RegisterWidth::U8 => reg.write_volatile(value),
+                RegisterWidth::U16 => reg.cast::<u16>().write_volatile(value.into()),
+                RegisterWidth::U32 => reg.cast::<u32>().write_volatile(value.into()),
+                RegisterWidth::U32Be => reg.cast::<u32>().write_volatile(u32::from(value).to_be()),
             }
-            Ok(())
-        } else {
-            Err(WouldBlockError)
         }
+        self.fence();
     }
 
-    /// Receives a byte on the serial port.
-    pub fn receive(&mut self) -> u8 {
-        retry_until_ok!(self.try_receive())
+    fn state(&self) -> &State {
+        &self.state
     }
 
-    /// Tries to receive a byte on the serial port.
-    pub fn try_receive(&mut self) -> Result<u8, WouldBlockError> {
-        if self.line_sts().contains(LineStsFlags::INPUT_FULL) {
-            let self_data = self.data.load(Ordering::Relaxed);
-            let data = unsafe { self_data.read() };
-            Ok(data)
-        } else {
-            Err(WouldBlockError)
-        }
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
     }
 }
 
+impl Uart16550 for MmioSerialPort {}
+
+impl_inherent_methods!(MmioSerialPort);
+
+/// Writes the string through [`send`](Uart16550::send).
+///
+/// As `core` implements [`fmt::Write`] for all `&mut W` where `W: fmt::Write`, a mutable
+/// reference to the port can be passed wherever a writer is expected:
+///
+/// ```no_run
+/// use core::fmt::Write;
+/// use uart_16550::{MmioSerialPort, Uart16550};
+///
+/// fn log<W: Write>(mut writer: W) {
+///     writeln!(writer, "hello").unwrap();
+/// }
+///
+/// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+/// serial_port.init();
+/// log(&mut serial_port);
+/// ```
 impl fmt::Write for MmioSerialPort {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for byte in s.bytes() {
-            self.send(byte);
+        uart::write_str(self, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+    use crate::DataBits;
+
+    // Plain memory echoes writes, so these tests only check the register layout. The
+    // registers are prepared through the base pointer and inspected after the last use of
+    // the port.
+
+    #[test]
+    fn stride() {
+        let mut registers = [0u8; 32];
+        registers[5 * 4] = 1 << 5;
+        let base = registers.as_mut_ptr();
+        let mut serial_port = unsafe { MmioSerialPort::new_with_stride(base as usize, 4) };
+        serial_port.init();
+        serial_port.send_raw(b'a');
+        assert_eq!(serial_port.stride(), 4);
+        assert_eq!(registers[0], b'a');
+        assert_eq!(registers[4], 0b0000_0001);
+        assert_eq!(registers[3 * 4], 0b0000_0011);
+        assert_eq!(registers[4 * 4], 0b0000_1011);
+        assert_eq!(registers[1..4], [0; 3]);
+    }
+
+    #[test]
+    fn reg_shift() {
+        let mut registers = [0u8; 32];
+        let base = registers.as_mut_ptr() as usize;
+        let mut serial_port = unsafe { MmioSerialPort::from_reg_shift(base, 2) };
+        serial_port.init();
+        serial_port.set_data_bits(DataBits::Seven);
+        assert_eq!(registers[3 * 4], 0b0000_0010);
+        assert_eq!(registers[4 * 4], 0b0000_1011);
+        assert!(registers
+            .iter()
+            .enumerate()
+            .all(|(i, &r)| i % 4 == 0 || r == 0));
+    }
+
+    #[test]
+    fn registers_16bit() {
+        let mut registers = [0u16; 16];
+        let base = registers.as_mut_ptr() as usize;
+        let mut serial_port = unsafe { MmioSerialPort::new_16bit(base, 4) };
+        serial_port.init();
+        serial_port.set_data_bits(DataBits::Seven);
+        assert_eq!(registers[6], 0b0000_0010);
+        assert_eq!(registers[8], 0b0000_1011);
+        assert!(registers.iter().skip(1).step_by(2).all(|&r| r == 0));
+    }
+
+    #[test]
+    fn registers_32bit() {
+        let mut registers = [0u32; 8];
+        let base = registers.as_mut_ptr() as usize;
+        let mut serial_port = unsafe { MmioSerialPort::new_32bit(base, 4) };
+        serial_port.init();
+        serial_port.set_data_bits(DataBits::Seven);
+        assert_eq!(serial_port.register_width(), RegisterWidth::U32);
+        assert_eq!(registers[3], 0b0000_0010);
+        assert_eq!(registers[4], 0b0000_1011);
+    }
+
+    #[test]
+    fn registers_32bit_big_endian() {
+        let mut registers = [0u32; 8];
+        let base = registers.as_mut_ptr();
+        let mut serial_port = unsafe { MmioSerialPort::new_32bit_be(base as usize, 4) };
+        serial_port.init();
+        serial_port.set_data_bits(DataBits::Seven);
+        unsafe {
+            base.write_volatile(u32::from(b'x').to_be());
+            base.add(5).write_volatile(1u32.to_be());
         }
-        Ok(())
+        assert_eq!(serial_port.try_receive(), Ok(b'x'));
+        assert_eq!(registers[3], 0b0000_0010u32.to_be());
+        assert_eq!(registers[4], 0b0000_1011u32.to_be());
+    }
+
+    #[test]
+    fn raw_parts_round_trip() {
+        let mut registers = [0u32; 8];
+        let base = registers.as_mut_ptr().cast::<u8>();
+        let serial_port = unsafe { MmioSerialPort::from_raw_parts(base, 4, RegisterWidth::U32) };
+        let (ptr, stride, width) = serial_port.into_raw_parts();
+        assert_eq!((ptr, stride, width), (base, 4, RegisterWidth::U32));
+
+        let mut serial_port = unsafe { MmioSerialPort::from_raw_parts(ptr, stride, width) };
+        assert_eq!(serial_port.base_address(), base as usize);
+        serial_port.init();
+        assert_eq!(registers[3], 0b0000_0011);
+        assert_eq!(registers[4], 0b0000_1011);
+    }
+
+    #[test]
+    fn try_create_and_open() {
+        // Plain memory passes the loopback test, as the data register echoes the sent byte
+        let mut registers = [0u8; 8];
+        registers[5] = 1 | 1 << 5;
+        let base = registers.as_mut_ptr() as usize;
+        assert!(unsafe { MmioSerialPort::try_create(base) }.is_ok());
+        let config = LineConfig::new().baud(115_200);
+        assert!(unsafe { MmioSerialPort::open(base, config) }.is_ok());
+        assert_eq!(registers[3], 0b0000_0011);
+        assert_eq!(registers[4], 0b0000_1011);
+    }
+
+    #[test]
+    fn fenced_write() {
+        let mut registers = [0u8; 8];
+        registers[5] = 1 << 5;
+        let base = registers.as_mut_ptr() as usize;
+        let mut serial_port = unsafe { MmioSerialPort::new(base) };
+        serial_port.set_fenced(true);
+        serial_port.init();
+        writeln!(serial_port).unwrap();
+        assert_eq!(registers[0], b'\n');
     }
 }
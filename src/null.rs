@@ -0,0 +1,78 @@
+use core::fmt;
+
+use crate::{
+    driver::{Driver, Register, State, INT_ID, LINE_STS},
+    LineStsFlags, Uart16550,
+};
+
+/// A serial port that discards all output and never receives any data.
+///
+/// This allows building the same code for targets without a UART: the type implements
+/// [`Uart16550`] and [`fmt::Write`], so logging code written against these traits compiles
+/// unchanged, while everything that is sent is dropped.
+///
+/// ```
+/// use core::fmt::Write;
+/// use uart_16550::{NullSerialPort, Uart16550};
+///
+/// let mut serial_port = NullSerialPort::new();
+/// serial_port.init();
+/// writeln!(serial_port, "discarded").unwrap();
+/// assert!(serial_port.try_receive().is_err());
+/// ```
+///
+/// The port behaves like a UART without FIFOs and scratch register whose transmitter is
+/// always empty, so [`detect_variant`](Uart16550::detect_variant) reports an 8250 and
+/// [`probe`](Uart16550::probe) returns `false`. Note that the blocking receive methods
+/// [`receive`](Uart16550::receive) and [`receive_until`](Uart16550::receive_until) never
+/// return, as no data ever arrives.
+#[derive(Debug)]
+pub struct NullSerialPort {
+    state: State,
+}
+
+impl NullSerialPort {
+    /// Creates a new null serial port.
+    pub const fn new() -> Self {
+        Self {
+            state: State::new(),
+        }
+    }
+}
+
+impl Default for NullSerialPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Driver for NullSerialPort {
+    /// Reports an empty transmitter, no pending interrupt and zero for all other registers.
+    fn read(&mut self, reg: Register) -> u8 {
+        match reg {
+            LINE_STS => (LineStsFlags::OUTPUT_EMPTY | LineStsFlags::TRANSMITTER_EMPTY).bits(),
+            INT_ID => 0x01,
+            _ => 0,
+        }
+    }
+
+    /// Discards the value.
+    fn write(&mut self, _reg: Register, _value: u8) {}
+
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+}
+
+impl Uart16550 for NullSerialPort {}
+
+/// Discards the string.
+impl fmt::Write for NullSerialPort {
+    fn write_str(&mut self, _s: &str) -> fmt::Result {
+        Ok(())
+    }
+}
@@ -1,156 +1,164 @@
 use core::fmt;
 
-use crate::{LineStsFlags, WouldBlockError};
+use crate::{
+    driver::{Driver, Register, State},
+    uart, InitError, LineConfig, LoopbackTestError, Uart16550,
+};
 
 /// A x86 I/O port-mapped UART.
 #[cfg_attr(docsrs, doc(cfg(any(target_arch = "x86", target_arch = "x86_64"))))]
 #[derive(Debug)]
-pub struct SerialPort(u16 /* base port */);
+pub struct SerialPort {
+    base: u16,
+    stride: u16,
+    state: State,
+}
 
 impl SerialPort {
-    /// Base port.
-    fn port_base(&self) -> u16 {
-        self.0
-    }
-
-    /// Data port.
+    /// Returns the base port of the UART.
     ///
-    /// Read and write.
-    fn port_data(&self) -> u16 {
-        self.port_base()
+    /// This is the port of the data register, which was passed when creating the port.
+    pub fn base_port(&self) -> u16 {
+        self.base
     }
 
-    /// Interrupt enable port.
-    ///
-    /// Write only.
-    fn port_int_en(&self) -> u16 {
-        self.port_base() + 1
+    /// Returns the distance between the ports of two consecutive registers.
+    pub fn stride(&self) -> u16 {
+        self.stride
     }
 
-    /// Fifo control port.
-    ///
-    /// Write only.
-    fn port_fifo_ctrl(&self) -> u16 {
-        self.port_base() + 2
+    /// Port of the register at the given offset.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn port_reg(&self, offset: u16) -> u16 {
+        self.base_port()
+            .wrapping_add(offset.wrapping_mul(self.stride))
     }
 
-    /// Line control port.
+    /// Creates a new serial port interface on the given I/O base port.
     ///
-    /// Write only.
-    fn port_line_ctrl(&self) -> u16 {
-        self.port_base() + 3
-    }
-
-    /// Modem control port.
+    /// # Safety
     ///
-    /// Write only.
-    fn port_modem_ctrl(&self) -> u16 {
-        self.port_base() + 4
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the caller has the necessary rights
+    /// to perform the I/O operation.
+    pub const unsafe fn new(base: u16) -> Self {
+        Self::new_with_stride(base, 1)
     }
 
-    /// Line status port.
+    /// Creates a new serial port interface on the given I/O base port with a given register
+    /// stride.
     ///
-    /// Read only.
-    fn port_line_sts(&self) -> u16 {
-        self.port_base() + 5
-    }
-
-    /// Creates a new serial port interface on the given I/O base port.
+    /// The registers are located at `base + n * stride`, which is used e.g. by some multiport
+    /// and PCI serial cards. Register ports beyond `0xFFFF` wrap around to the start of the
+    /// I/O port space.
+    ///
+    /// # Safety
     ///
     /// This function is unsafe because the caller must ensure that the given base address
     /// really points to a serial port device and that the caller has the necessary rights
     /// to perform the I/O operation.
-    pub const unsafe fn new(base: u16) -> Self {
-        Self(base)
+    pub const unsafe fn new_with_stride(base: u16, stride: u16) -> Self {
+        Self {
+            base,
+            stride,
+            state: State::new(),
+        }
     }
 
-    /// Initializes the serial port.
+    /// Creates and initializes a new UART interface on the given base address and checks
+    /// that it works using [`loopback_test`](Self::loopback_test).
     ///
-    /// The default configuration of [38400/8-N-1](https://en.wikipedia.org/wiki/8-N-1) is used.
-    pub fn init(&mut self) {
-        unsafe {
-            // Disable interrupts
-            x86::io::outb(self.port_int_en(), 0x00);
-
-            // Enable DLAB
-            x86::io::outb(self.port_line_ctrl(), 0x80);
-
-            // Set maximum speed to 38400 bps by configuring DLL and DLM
-            x86::io::outb(self.port_data(), 0x03);
-            x86::io::outb(self.port_int_en(), 0x00);
-
-            // Disable DLAB and set data word length to 8 bits
-            x86::io::outb(self.port_line_ctrl(), 0x03);
-
-            // Enable FIFO, clear TX/RX queues and
-            // set interrupt watermark at 14 bytes
-            x86::io::outb(self.port_fifo_ctrl(), 0xc7);
-
-            // Mark data terminal ready, signal request to send
-            // and enable auxilliary output #2 (used as interrupt line for CPU)
-            x86::io::outb(self.port_modem_ctrl(), 0x0b);
-
-            // Enable interrupts
-            x86::io::outb(self.port_int_en(), 0x01);
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the caller has the necessary rights
+    /// to perform the I/O operation.
+    pub unsafe fn try_create(base: u16) -> Result<Self, LoopbackTestError> {
+        let mut serial_port = Self::new(base);
+        serial_port.init();
+        if let Err(err) = serial_port.loopback_test() {
+            // dropping would wait for the transmitter that just failed the test
+            serial_port.into_raw();
+            return Err(err);
         }
+        Ok(serial_port)
     }
 
-    fn line_sts(&mut self) -> LineStsFlags {
-        unsafe { LineStsFlags::from_bits_truncate(x86::io::inb(self.port_line_sts())) }
+    /// Creates a new UART interface on the given base port, initializes it with the given
+    /// line configuration and checks that it works using
+    /// [`loopback_test`](Self::loopback_test).
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because the caller must ensure that the given base address
+    /// really points to a serial port device and that the caller has the necessary rights
+    /// to perform the I/O operation.
+    pub unsafe fn open(base: u16, config: LineConfig) -> Result<Self, InitError> {
+        let mut serial_port = Self::new(base);
+        serial_port.init();
+        let result = serial_port
+            .configure(config)
+            .map_err(InitError::from)
+            .and_then(|()| serial_port.loopback_test().map_err(InitError::from));
+        if let Err(err) = result {
+            // dropping would wait for the transmitter that just failed the test
+            serial_port.into_raw();
+            return Err(err);
+        }
+        Ok(serial_port)
     }
 
-    /// Sends a byte on the serial port.
-    pub fn send(&mut self, data: u8) {
-        match data {
-            8 | 0x7F => {
-                self.send_raw(8);
-                self.send_raw(b' ');
-                self.send_raw(8);
-            }
-            data => {
-                self.send_raw(data);
-            }
-        }
+    /// Consumes the serial port without flushing it and returns its base port.
+    ///
+    /// Dropping a `SerialPort` waits until all pending output has been transmitted. Use this
+    /// function instead where blocking is undesirable, e.g. in interrupt handlers or when
+    /// the transmitter might be stalled by hardware flow control.
+    pub fn into_raw(self) -> u16 {
+        let serial_port = core::mem::ManuallyDrop::new(self);
+        serial_port.base
     }
+}
 
-    /// Sends a raw byte on the serial port, intended for binary data.
-    pub fn send_raw(&mut self, data: u8) {
-        retry_until_ok!(self.try_send_raw(data))
+impl Driver for SerialPort {
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn read(&mut self, reg: Register) -> u8 {
+        unsafe { x86::io::inb(self.port_reg(reg.0.into())) }
     }
 
-    /// Tries to send a raw byte on the serial port, intended for binary data.
-    pub fn try_send_raw(&mut self, data: u8) -> Result<(), WouldBlockError> {
-        if self.line_sts().contains(LineStsFlags::OUTPUT_EMPTY) {
-            unsafe {
-                x86::io::outb(self.port_data(), data);
-            }
-            Ok(())
-        } else {
-            Err(WouldBlockError)
-        }
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn write(&mut self, reg: Register, value: u8) {
+        unsafe { x86::io::outb(self.port_reg(reg.0.into()), value) }
     }
 
-    /// Receives a byte on the serial port.
-    pub fn receive(&mut self) -> u8 {
-        retry_until_ok!(self.try_receive())
+    fn state(&self) -> &State {
+        &self.state
     }
 
-    /// Tries to receive a byte on the serial port.
-    pub fn try_receive(&mut self) -> Result<u8, WouldBlockError> {
-        if self.line_sts().contains(LineStsFlags::INPUT_FULL) {
-            let data = unsafe { x86::io::inb(self.port_data()) };
-            Ok(data)
-        } else {
-            Err(WouldBlockError)
-        }
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
     }
 }
 
+impl Uart16550 for SerialPort {}
+
+impl_inherent_methods!(SerialPort);
+
 impl fmt::Write for SerialPort {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for byte in s.bytes() {
-            self.send(byte);
-        }
-        Ok(())
+        uart::write_str(self, s)
+    }
+}
+
+/// Waits until all pending output has been transmitted, see [`Uart16550::flush`].
+///
+/// This blocks for as long as the transmitter does not become empty, which may be forever if
+/// it is stalled by hardware flow control. Use [`SerialPort::into_raw`] to drop the port
+/// without waiting.
+impl Drop for SerialPort {
+    fn drop(&mut self) {
+        self.flush();
     }
 }
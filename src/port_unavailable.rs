@@ -0,0 +1,28 @@
+/// Marker for types that can address an I/O port, which is implemented for none of them.
+#[diagnostic::on_unimplemented(
+    message = "port-mapped I/O is only available on x86, use `MmioSerialPort` instead",
+    label = "`SerialPort` requires the x86 `in` and `out` instructions",
+    note = "use `uart_16550::MmioSerialPort` to access a memory-mapped UART"
+)]
+pub trait PortIoAvailable {}
+
+/// Port-mapped I/O is only available on x86, use [`MmioSerialPort`](crate::MmioSerialPort).
+///
+/// This type only exists to point users of port-mapped I/O to the memory-mapped
+/// implementation with a descriptive compiler error. It can not be constructed.
+#[deprecated(note = "port-mapped I/O is only available on x86, use `MmioSerialPort` instead")]
+pub struct SerialPort {
+    _unconstructible: core::convert::Infallible,
+}
+
+#[allow(deprecated)]
+impl SerialPort {
+    /// Fails to compile, as port-mapped I/O is only available on x86.
+    ///
+    /// # Safety
+    ///
+    /// This function can not be called.
+    pub const unsafe fn new<B: PortIoAvailable>(_base: B) -> Self {
+        panic!("port-mapped I/O is only available on x86")
+    }
+}
@@ -0,0 +1,111 @@
+use core::fmt;
+
+use crate::{
+    driver::{Driver, Register, State},
+    uart, Uart16550,
+};
+
+/// Access to the registers of a 16550 compatible UART by register offset.
+///
+/// Implement this trait for UARTs whose registers are neither port-mapped nor plain
+/// memory-mapped, e.g. registers behind a lock or accessed through a hypervisor call, and
+/// wrap the implementation in a [`GenericSerialPort`] to get the whole driver on top of it.
+///
+/// The driver accesses the standard registers at offsets `0` to `7`. Depending on the
+/// divisor latch access bit of the line control register, offsets `0` and `1` address
+/// the divisor latch instead of the data and interrupt enable registers, which the
+/// implementation does not need to care about. Other offsets are only passed through
+/// [`Uart16550::read_register`] and [`Uart16550::write_register`].
+pub trait RegisterAccess {
+    /// Reads the register at the given offset.
+    fn read(&mut self, offset: u8) -> u8;
+
+    /// Writes the register at the given offset.
+    fn write(&mut self, offset: u8, value: u8);
+}
+
+/// A UART whose registers are accessed through a user supplied [`RegisterAccess`].
+///
+/// ```
+/// use uart_16550::{GenericSerialPort, RegisterAccess, Uart16550};
+///
+/// /// Registers that are backed by plain memory, e.g. of an emulated UART
+/// struct Registers([u8; 8]);
+///
+/// impl RegisterAccess for Registers {
+///     fn read(&mut self, offset: u8) -> u8 {
+///         self.0[usize::from(offset)]
+///     }
+///
+///     fn write(&mut self, offset: u8, value: u8) {
+///         self.0[usize::from(offset)] = value;
+///     }
+/// }
+///
+/// let mut registers = Registers([0; 8]);
+/// // Report an empty transmitter
+/// registers.0[5] = 1 << 5 | 1 << 6;
+/// let mut serial_port = GenericSerialPort::new(registers);
+/// serial_port.init();
+/// serial_port.send_bytes(b"Hello world!");
+/// assert_eq!(serial_port.registers().0[0], b'!');
+/// ```
+#[derive(Debug)]
+pub struct GenericSerialPort<R> {
+    registers: R,
+    state: State,
+}
+
+impl<R: RegisterAccess> GenericSerialPort<R> {
+    /// Creates a new UART interface on top of the given register accesses.
+    ///
+    /// The UART is not touched, so it must be initialized, e.g. through
+    /// [`init`](Uart16550::init), before use.
+    pub const fn new(registers: R) -> Self {
+        Self {
+            registers,
+            state: State::new(),
+        }
+    }
+
+    /// Returns a reference to the register accesses.
+    pub fn registers(&self) -> &R {
+        &self.registers
+    }
+
+    /// Unwraps the register accesses.
+    pub fn into_inner(self) -> R {
+        self.registers
+    }
+}
+
+impl<R: RegisterAccess> Driver for GenericSerialPort<R> {
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn read(&mut self, reg: Register) -> u8 {
+        self.registers.read(reg.0)
+    }
+
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn write(&mut self, reg: Register, value: u8) {
+        self.registers.write(reg.0, value);
+    }
+
+    fn state(&self) -> &State {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+}
+
+impl<R: RegisterAccess> Uart16550 for GenericSerialPort<R> {}
+
+/// Writes the string through [`send`](Uart16550::send).
+impl<R: RegisterAccess> fmt::Write for GenericSerialPort<R> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        uart::write_str(self, s)
+    }
+}
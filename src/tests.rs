@@ -0,0 +1,1117 @@
+extern crate std;
+
+use core::{
+    cell::{Cell, RefCell, RefMut},
+    fmt::Write,
+};
+use std::{collections::VecDeque, vec::Vec};
+
+use crate::{
+    DataBits, FifoStatus, FlowControl, GenericSerialPort, IntEnFlags, LineConfig, LineStsFlags,
+    LoopbackTestError, ModemCtrlFlags, ModemStsFlags, Parity, ReadResult, ReceiveError,
+    RegisterAccess, SendError, StopBits, Uart16550, UartVariant,
+};
+
+/// Registers of a simulated UART.
+///
+/// Unlike plain memory, the read and write sides of the registers are separate, as on real
+/// hardware: received bytes are queued for reads of the data register, sent bytes are
+/// collected, the line and modem status are derived from the simulated state and the
+/// divisor latch is selected through DLAB.
+#[derive(Debug)]
+pub(crate) struct Registers {
+    /// Bytes waiting in the receive FIFO.
+    pub(crate) rx: VecDeque<u8>,
+    /// Bytes that were sent.
+    pub(crate) tx: Vec<u8>,
+    /// Whether the transmitter holding register accepts a byte.
+    pub(crate) tx_ready: bool,
+    /// Whether the transmitter shift register is empty.
+    pub(crate) tx_idle: bool,
+    /// Receive error bits of the line status, cleared by reading it.
+    pub(crate) errors: LineStsFlags,
+    /// Modem status inputs, whose delta bits are cleared by reading them.
+    pub(crate) modem_sts: ModemStsFlags,
+    /// Pending interrupt as reported in bits 0 to 3 of the interrupt identification.
+    pub(crate) int_id: u8,
+    /// FIFO status reported in bits 6 and 7 of the interrupt identification.
+    pub(crate) fifo_bits: u8,
+    /// Whether the 64 byte FIFO of the 16750 can be enabled.
+    pub(crate) fifo_64: bool,
+    /// Whether the chip has a scratch register.
+    pub(crate) has_scratch: bool,
+    /// Whether bytes sent in loopback mode are received again.
+    pub(crate) loopback_connected: bool,
+    /// Data bits that are stuck high on the loopback path.
+    pub(crate) loopback_stuck_high: u8,
+    /// Whether no device answers, so that all registers read as all ones.
+    pub(crate) absent: bool,
+    /// Number of reads of the line status register.
+    pub(crate) line_sts_reads: usize,
+    pub(crate) divisor: u16,
+    pub(crate) int_en: u8,
+    /// Last value written to the FIFO control register, including the self-clearing bits.
+    pub(crate) fifo_ctrl: u8,
+    pub(crate) line_ctrl: u8,
+    pub(crate) modem_ctrl: u8,
+    pub(crate) scratch: u8,
+    /// Enhanced feature register, accessible while the line control register is `0xBF`.
+    pub(crate) efr: u8,
+}
+
+impl Registers {
+    fn dlab(&self) -> bool {
+        self.line_ctrl & 0x80 != 0
+    }
+
+    fn loopback(&self) -> bool {
+        self.modem_ctrl & ModemCtrlFlags::LOOPBACK.bits() != 0
+    }
+
+    fn read(&mut self, offset: u8) -> u8 {
+        if self.absent {
+            return 0xFF;
+        }
+        match offset {
+            0 if self.dlab() => self.divisor as u8,
+            0 => self.rx.pop_front().unwrap_or(0),
+            1 if self.dlab() => (self.divisor >> 8) as u8,
+            1 => self.int_en,
+            2 if self.line_ctrl == 0xBF => self.efr,
+            2 if self.fifo_ctrl & 1 != 0 => {
+                let fifo_64 = if self.fifo_64 {
+                    self.fifo_ctrl & 1 << 5
+                } else {
+                    0
+                };
+                self.fifo_bits << 6 | fifo_64 | self.int_id
+            }
+            2 => self.int_id,
+            3 => self.line_ctrl,
+            4 => self.modem_ctrl,
+            5 => {
+                self.line_sts_reads += 1;
+                let mut line_sts = core::mem::replace(&mut self.errors, LineStsFlags::empty());
+                line_sts.set(LineStsFlags::INPUT_FULL, !self.rx.is_empty());
+                line_sts.set(LineStsFlags::OUTPUT_EMPTY, self.tx_ready);
+                line_sts.set(LineStsFlags::TRANSMITTER_EMPTY, self.tx_idle);
+                line_sts.bits()
+            }
+            6 if self.loopback() => {
+                // DTR, RTS, OUT1 and OUT2 are looped back to DSR, CTS, RING and DCD
+                let mcr = self.modem_ctrl;
+                (mcr & 0b1 << 5) | (mcr & 0b10) << 3 | (mcr & 0b1100) << 4
+            }
+            6 => {
+                let modem_sts = self.modem_sts.bits();
+                self.modem_sts = ModemStsFlags::from_bits_retain(modem_sts & 0xF0);
+                modem_sts
+            }
+            7 if self.has_scratch => self.scratch,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        match offset {
+            0 if self.dlab() => self.divisor = self.divisor & 0xFF00 | u16::from(value),
+            0 if self.loopback() => {
+                if self.loopback_connected {
+                    self.rx.push_back(value | self.loopback_stuck_high);
+                }
+            }
+            0 => self.tx.push(value),
+            1 if self.dlab() => self.divisor = self.divisor & 0x00FF | u16::from(value) << 8,
+            1 => self.int_en = value,
+            2 if self.line_ctrl == 0xBF => self.efr = value,
+            2 => {
+                if value & 1 << 1 != 0 {
+                    self.rx.clear();
+                }
+                self.fifo_ctrl = value;
+            }
+            3 => self.line_ctrl = value,
+            4 => self.modem_ctrl = value,
+            7 => self.scratch = value,
+            _ => {}
+        }
+    }
+}
+
+/// A simulated 16550A, whose registers can be inspected and changed while a port uses it.
+#[derive(Debug)]
+pub(crate) struct MockUart(RefCell<Registers>);
+
+impl MockUart {
+    pub(crate) fn new() -> Self {
+        Self(RefCell::new(Registers {
+            rx: VecDeque::new(),
+            tx: Vec::new(),
+            tx_ready: true,
+            tx_idle: true,
+            errors: LineStsFlags::empty(),
+            modem_sts: ModemStsFlags::empty(),
+            int_id: 0x01,
+            fifo_bits: 0b11,
+            fifo_64: false,
+            has_scratch: true,
+            loopback_connected: true,
+            loopback_stuck_high: 0,
+            absent: false,
+            line_sts_reads: 0,
+            divisor: 0,
+            int_en: 0,
+            fifo_ctrl: 0,
+            line_ctrl: 0,
+            modem_ctrl: 0,
+            scratch: 0,
+            efr: 0,
+        }))
+    }
+
+    /// Returns the registers. The borrow must end before the port is used again.
+    pub(crate) fn regs(&self) -> RefMut<'_, Registers> {
+        self.0.borrow_mut()
+    }
+
+    /// Returns a port on top of this UART that was not initialized.
+    pub(crate) fn port(&self) -> GenericSerialPort<&Self> {
+        GenericSerialPort::new(self)
+    }
+
+    /// Returns an initialized port on top of this UART.
+    pub(crate) fn init_port(&self) -> GenericSerialPort<&Self> {
+        let mut port = self.port();
+        port.init();
+        port
+    }
+}
+
+impl RegisterAccess for &MockUart {
+    fn read(&mut self, offset: u8) -> u8 {
+        self.regs().read(offset)
+    }
+
+    fn write(&mut self, offset: u8, value: u8) {
+        self.regs().write(offset, value);
+    }
+}
+
+#[test]
+fn init_programs_default_configuration() {
+    let uart = MockUart::new();
+    let port = uart.init_port();
+    let regs = uart.regs();
+    assert_eq!(regs.divisor, 3);
+    assert_eq!(regs.int_en, 0b0000_0001);
+    assert_eq!(regs.fifo_ctrl, 0b1100_0111);
+    assert_eq!(regs.line_ctrl, 0b0000_0011);
+    assert_eq!(regs.modem_ctrl, 0b0000_1011);
+    assert_eq!(port.current_config(), LineConfig::new());
+}
+
+#[test]
+fn init_polling_leaves_interrupts_disabled() {
+    let uart = MockUart::new();
+    let mut port = uart.port();
+    port.init_polling();
+    assert!(port.enabled_interrupts().is_empty());
+    assert_eq!(uart.regs().int_en, 0);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+}
+
+#[test]
+fn init_uses_closest_baud_rate_for_inexact_clock() {
+    let uart = MockUart::new();
+    let mut port = uart.port();
+    // 3 MHz / (16 * 5) = 37500 baud, which is 2.3% off
+    port.set_clock_frequency(3_000_000);
+    port.init();
+    assert_eq!(uart.regs().divisor, 5);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+    assert_eq!(port.current_config(), LineConfig::new().baud(37_500));
+
+    // The clock is too slow for 38400 baud
+    port.set_clock_frequency(100_000);
+    port.init();
+    assert_eq!(uart.regs().divisor, 1);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+}
+
+#[test]
+fn init_preserve_baud_keeps_divisor() {
+    let uart = MockUart::new();
+    uart.regs().divisor = 1;
+    let mut port = uart.port();
+    port.init_preserve_baud();
+    assert_eq!(uart.regs().divisor, 1);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+    assert_eq!(port.current_config(), LineConfig::new().baud(115_200));
+}
+
+#[test]
+fn shutdown_clears_interrupts_and_modem_control() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.shutdown();
+    assert_eq!(uart.regs().int_en, 0);
+    assert_eq!(uart.regs().modem_ctrl, 0);
+}
+
+#[test]
+fn restore_reapplies_snapshot() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_baud_rate(115_200).unwrap();
+    let snapshot = port.capture();
+
+    *uart.regs() = MockUart::new().0.into_inner();
+    port.restore(&snapshot);
+    let regs = uart.regs();
+    assert_eq!(regs.divisor, 1);
+    assert_eq!(regs.int_en, 0b0000_0001);
+    assert_eq!(regs.fifo_ctrl, 0b1100_0001);
+    assert_eq!(regs.line_ctrl, 0b0000_0011);
+    assert_eq!(regs.modem_ctrl, 0b0000_1011);
+    drop(regs);
+    assert_eq!(port.capture(), snapshot);
+}
+
+#[test]
+fn loopback_test_passes_and_restores_modem_control() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(
+        port.loopback_test_with_data(&[0x00, 0xFF, 0x55, 0xAA]),
+        Ok(())
+    );
+    assert_eq!(uart.regs().modem_ctrl, 0b0000_1011);
+    assert!(uart.regs().tx.is_empty());
+}
+
+#[test]
+fn loopback_test_reports_lost_and_corrupted_bytes() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().loopback_stuck_high = 0x80;
+    assert_eq!(
+        port.loopback_test_with_data(&[0x55]),
+        Err(LoopbackTestError::Mismatch {
+            expected: 0x55,
+            received: 0xD5,
+        })
+    );
+
+    uart.regs().loopback_connected = false;
+    assert_eq!(
+        port.loopback_test_with_data(&[0x55]),
+        Err(LoopbackTestError::Timeout { expected: 0x55 })
+    );
+    assert_eq!(uart.regs().modem_ctrl, 0b0000_1011);
+}
+
+#[test]
+fn loopback_test_gives_up_on_wedged_transmitter() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().tx_ready = false;
+    assert_eq!(
+        port.loopback_test(),
+        Err(LoopbackTestError::Timeout { expected: 0xAE })
+    );
+    assert!(port.loopback_diagnose().timed_out());
+    assert_eq!(uart.regs().modem_ctrl, 0b0000_1011);
+
+    uart.regs().tx_ready = true;
+    port.set_tx_enabled(false);
+    assert_eq!(
+        port.loopback_test(),
+        Err(LoopbackTestError::Timeout { expected: 0xAE })
+    );
+}
+
+#[test]
+fn loopback_diagnose_reports_stuck_bits() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    let diagnosis = port.loopback_diagnose();
+    assert!(diagnosis.is_ok());
+    assert_eq!(diagnosis.faulty_bits(), 0);
+
+    uart.regs().loopback_stuck_high = 0x81;
+    let diagnosis = port.loopback_diagnose();
+    assert!(!diagnosis.is_ok());
+    assert_eq!(diagnosis.stuck_high(), 0x81);
+    assert_eq!(diagnosis.stuck_low(), 0);
+    assert!(!diagnosis.timed_out());
+}
+
+#[test]
+fn configure_leaves_fifo_and_modem_control_alone() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    let fifo_ctrl = uart.regs().fifo_ctrl;
+    port.configure(LineConfig::new().baud(115_200)).unwrap();
+    assert_eq!(uart.regs().divisor, 1);
+    assert_eq!(uart.regs().fifo_ctrl, fifo_ctrl);
+    assert_eq!(uart.regs().modem_ctrl, 0b0000_1011);
+}
+
+#[test]
+fn current_config_tracks_setters() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_baud_rate(115_200).unwrap();
+    port.set_parity(Parity::Even);
+    assert_eq!(
+        port.current_config(),
+        LineConfig::new().baud(115_200).parity(Parity::Even)
+    );
+}
+
+#[test]
+fn current_config_getters() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_data_bits(DataBits::Seven);
+    port.set_parity(Parity::Odd);
+    port.set_stop_bits(StopBits::Two).unwrap();
+    let config = port.current_config();
+    assert_eq!(config.baud_rate(), 38_400);
+    assert_eq!(config.get_data_bits(), DataBits::Seven);
+    assert_eq!(config.get_parity(), Parity::Odd);
+    assert_eq!(config.get_stop_bits(), StopBits::Two);
+}
+
+#[test]
+fn set_data_bits_and_parity() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_data_bits(DataBits::Seven);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0010);
+    port.set_data_bits(DataBits::Eight);
+    port.set_parity(Parity::Even);
+    assert_eq!(port.parity(), Parity::Even);
+    assert_eq!(uart.regs().line_ctrl, 0b0001_1011);
+}
+
+#[test]
+fn set_stop_bits_depends_on_word_length() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_stop_bits(StopBits::Two).unwrap();
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0111);
+    assert!(port.set_stop_bits(StopBits::OneAndHalf).is_err());
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0111);
+    port.set_stop_bits(StopBits::One).unwrap();
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+
+    port.set_data_bits(DataBits::Five);
+    port.set_stop_bits(StopBits::OneAndHalf).unwrap();
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0100);
+    assert!(port.set_stop_bits(StopBits::Two).is_err());
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0100);
+}
+
+#[test]
+fn set_break_toggles_break_bit() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_break(true);
+    assert_eq!(uart.regs().line_ctrl, 0b0100_0011);
+    port.set_break(false);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+}
+
+#[cfg(feature = "embedded-hal")]
+#[test]
+fn send_break_for_releases_break() {
+    struct NoDelay(u32);
+
+    impl embedded_hal::delay::DelayNs for NoDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += ns;
+        }
+    }
+
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    let mut delay = NoDelay(0);
+    port.send_break_for(&mut delay, 1000);
+    assert_eq!(delay.0, 1000);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+}
+
+#[test]
+fn write_divisor_keeps_line_control() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.write_divisor(12);
+    assert_eq!(uart.regs().divisor, 12);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+    assert_eq!(port.read_divisor(), 12);
+    assert_eq!(port.current_config(), LineConfig::new().baud(9600));
+}
+
+#[test]
+fn set_clock_frequency_changes_divisor() {
+    let uart = MockUart::new();
+    let mut port = uart.port();
+    port.set_clock_frequency(24_000_000);
+    port.init();
+    port.set_baud_rate(115_200).unwrap();
+    assert_eq!(uart.regs().divisor, 13);
+}
+
+#[test]
+fn disabled_transmitter_sends_nothing() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_tx_enabled(false);
+    assert!(port.try_send_raw(b'a').is_err());
+    assert!(write!(port, "b").is_err());
+    assert!(uart.regs().tx.is_empty());
+    port.set_tx_enabled(true);
+    port.send(b'c');
+    assert_eq!(uart.regs().tx, b"c");
+}
+
+#[test]
+fn send_translates_backspace() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.send(0x7F);
+    assert_eq!(uart.regs().tx, [8, b' ', 8]);
+    port.set_translation(false);
+    port.send(0x7F);
+    assert_eq!(uart.regs().tx, [8, b' ', 8, 0x7F]);
+}
+
+#[test]
+fn overrun_resets_receive_fifo_if_enabled() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"ab");
+    uart.regs().errors = LineStsFlags::OVERRUN_ERROR;
+    assert_eq!(port.try_receive_checked(), Err(ReceiveError::Overrun));
+    assert_eq!(port.try_receive_checked(), Ok(b'a'));
+
+    port.set_reset_on_overrun(true);
+    uart.regs().errors = LineStsFlags::OVERRUN_ERROR;
+    assert_eq!(port.try_receive_checked(), Err(ReceiveError::Overrun));
+    assert_eq!(uart.regs().fifo_ctrl, 0b1100_0011);
+    assert_eq!(port.try_receive_checked(), Err(ReceiveError::WouldBlock));
+}
+
+#[test]
+fn receive_errors_discard_byte() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"ab");
+    uart.regs().errors = LineStsFlags::FRAMING_ERROR;
+    assert_eq!(port.try_receive_checked(), Err(ReceiveError::Framing));
+    assert_eq!(port.try_receive_checked(), Ok(b'b'));
+}
+
+#[test]
+fn mask_received_clears_unused_bits() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_data_bits(DataBits::Seven);
+    uart.regs().rx.push_back(0xC1);
+    assert_eq!(port.try_receive(), Ok(0xC1));
+    port.set_mask_received(true);
+    uart.regs().rx.push_back(0xC1);
+    assert_eq!(port.try_receive(), Ok(0x41));
+}
+
+std::thread_local! {
+    static RELAXED: Cell<u32> = const { Cell::new(0) };
+}
+
+fn count_relax() {
+    RELAXED.with(|relaxed| relaxed.set(relaxed.get() + 1));
+}
+
+#[test]
+fn relax_is_called_once_per_interval() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_relax(count_relax);
+    assert!(port.try_receive_timeout(32).is_err());
+    assert_eq!(RELAXED.with(Cell::get), 32);
+
+    RELAXED.with(|relaxed| relaxed.set(0));
+    port.set_relax_interval(16);
+    assert!(port.try_receive_timeout(32).is_err());
+    assert_eq!(RELAXED.with(Cell::get), 2);
+}
+
+#[test]
+fn fifo_status_updates_fifo_depth() {
+    let uart = MockUart::new();
+    let mut port = uart.port();
+    assert_eq!(port.fifo_depth(), 1);
+    port.init();
+    assert_eq!(port.fifo_depth(), 16);
+
+    // A 16550 reports its FIFOs as unusable
+    uart.regs().fifo_bits = 0b10;
+    assert_eq!(port.fifo_status(), FifoStatus::Unusable);
+    assert_eq!(port.fifo_depth(), 1);
+}
+
+#[test]
+fn reset_fifos_keeps_trigger_level() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.reset_receive_fifo();
+    assert_eq!(uart.regs().fifo_ctrl, 0b1100_0011);
+    port.reset_transmit_fifo();
+    assert_eq!(uart.regs().fifo_ctrl, 0b1100_0101);
+}
+
+#[test]
+fn set_loopback_keeps_modem_control() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_loopback(true);
+    assert_eq!(uart.regs().modem_ctrl, 0b0001_1011);
+    port.set_loopback(false);
+    assert_eq!(uart.regs().modem_ctrl, 0b0000_1011);
+}
+
+#[test]
+fn flow_control_waits_for_cts() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_flow_control(FlowControl::RtsCts);
+    assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+    assert!(port.try_send_raw(42).is_err());
+    uart.regs().modem_sts = ModemStsFlags::CTS;
+    assert!(port.try_send_raw(42).is_ok());
+    assert_eq!(uart.regs().tx, [42]);
+}
+
+#[test]
+fn flow_control_keeps_rts_raised_for_single_byte_reads() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_flow_control(FlowControl::RtsCts);
+    uart.regs().rx.extend(b"abc");
+    for &expected in b"abc" {
+        // Read one byte like a `Read` implementation given a one byte buffer
+        assert_eq!(port.receive(), expected);
+        assert_eq!(port.receive_into(&mut []), 0);
+        assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+    }
+    assert_eq!(port.drain_receive_fifo(&mut []), 0);
+    assert_eq!(port.receive_into_checked(&mut []).count, 0);
+    assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+}
+
+#[test]
+fn flow_control_raises_rts_before_receiving() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_flow_control(FlowControl::RtsCts);
+    uart.regs().rx.extend(b"abcd");
+    let mut buf = [0; 2];
+    assert_eq!(port.receive_into(&mut buf), 2);
+    assert!(!port.modem_control().contains(ModemCtrlFlags::RTS));
+    assert_eq!(port.try_receive(), Ok(b'c'));
+    assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+
+    assert_eq!(port.receive_into(&mut buf[..1]), 1);
+    assert!(!port.modem_control().contains(ModemCtrlFlags::RTS));
+    uart.regs().rx.push_back(b'e');
+    assert_eq!(port.bytes().next(), Some(b'e'));
+    assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+}
+
+#[test]
+fn detect_variant_restores_fifo_configuration() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(port.detect_variant(), UartVariant::Uart16550A);
+    assert_eq!(uart.regs().fifo_ctrl, 0b1100_0001);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+    assert_eq!(port.fifo_depth(), 16);
+}
+
+#[test]
+fn detect_variant_tells_chips_apart() {
+    let detect = |setup: fn(&mut Registers)| {
+        let uart = MockUart::new();
+        setup(&mut uart.regs());
+        let mut port = uart.init_port();
+        (port.detect_variant(), port.fifo_depth())
+    };
+    assert_eq!(
+        detect(|regs| regs.fifo_64 = true),
+        (UartVariant::Uart16750, 16)
+    );
+    assert_eq!(
+        detect(|regs| regs.fifo_bits = 0b10),
+        (UartVariant::Uart16550, 1)
+    );
+    assert_eq!(
+        detect(|regs| regs.fifo_bits = 0),
+        (UartVariant::Uart16450, 1)
+    );
+    assert_eq!(
+        detect(|regs| {
+            regs.fifo_bits = 0;
+            regs.has_scratch = false;
+        }),
+        (UartVariant::Uart8250, 1)
+    );
+}
+
+#[test]
+fn auto_flow_control_requires_16750() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert!(port.enable_auto_flow_control(true).is_err());
+
+    let uart = MockUart::new();
+    uart.regs().fifo_64 = true;
+    let mut port = uart.init_port();
+    port.enable_auto_flow_control(true).unwrap();
+    assert_eq!(uart.regs().modem_ctrl, 0b0010_1011);
+}
+
+#[test]
+fn modem_status_event_reports_changes_once() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().modem_sts = ModemStsFlags::DELTA_CARRIER_DETECT | ModemStsFlags::CARRIER_DETECT;
+    let event = port.modem_status_event();
+    assert!(event.carrier_detect_changed() && event.carrier_detect());
+    assert!(!event.cts_changed());
+    let event = port.modem_status_event();
+    assert!(!event.any_changed() && event.carrier_detect());
+}
+
+#[test]
+fn raw_register_access() {
+    let uart = MockUart::new();
+    let mut port = uart.port();
+    unsafe { port.write_register(7, 0x5A) };
+    assert_eq!(uart.regs().scratch, 0x5A);
+    assert_eq!(unsafe { port.read_register(7) }, 0x5A);
+}
+
+#[test]
+fn enhanced_register_window_is_closed_again() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    let fifo_ctrl = uart.regs().fifo_ctrl;
+    unsafe {
+        port.with_enhanced_register(|port| {
+            let efr = port.read_register(2);
+            port.write_register(2, efr | 1 << 4);
+        });
+    }
+    assert_eq!(uart.regs().efr, 1 << 4);
+    assert_eq!(uart.regs().fifo_ctrl, fifo_ctrl);
+    assert_eq!(uart.regs().line_ctrl, 0b0000_0011);
+}
+
+#[test]
+fn probe_needs_scratch_register() {
+    let uart = MockUart::new();
+    uart.regs().scratch = 0x42;
+    assert!(uart.port().probe());
+    assert_eq!(uart.regs().scratch, 0x42);
+
+    uart.regs().has_scratch = false;
+    assert!(!uart.port().probe());
+}
+
+#[test]
+fn line_status_predicates() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert!(!port.is_data_available());
+    uart.regs().rx.push_back(b'a');
+    assert!(port.is_data_available());
+
+    // The last byte moved into the shift register, but was not sent completely yet
+    uart.regs().tx_idle = false;
+    assert!(port.is_transmit_ready());
+    assert!(!port.transmitter_idle());
+}
+
+#[test]
+fn take_errors_keeps_data() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.push_back(b'a');
+    uart.regs().errors = LineStsFlags::PARITY_ERROR;
+    assert_eq!(port.take_errors(), LineStsFlags::PARITY_ERROR);
+    assert_eq!(port.take_errors(), LineStsFlags::empty());
+    assert_eq!(port.try_receive(), Ok(b'a'));
+}
+
+#[test]
+fn send_raw_counted_counts_waits() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(port.send_raw_counted(42), 0);
+    assert_eq!(uart.regs().tx, [42]);
+}
+
+#[test]
+fn send_raw_timeout_gives_up() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().tx_ready = false;
+    assert!(port.send_raw_timeout(42, 1000).is_err());
+    assert!(uart.regs().tx.is_empty());
+    uart.regs().tx_ready = true;
+    assert!(port.send_raw_timeout(42, 1000).is_ok());
+    assert_eq!(uart.regs().tx, [42]);
+}
+
+#[test]
+fn try_send_checked_detects_faults() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(port.try_send_checked(b'a'), Ok(()));
+    uart.regs().errors = LineStsFlags::BREAK_INTERRUPT;
+    assert_eq!(port.try_send_checked(b'b'), Err(SendError::Break));
+    uart.regs().absent = true;
+    assert_eq!(port.try_send_checked(b'c'), Err(SendError::NoDevice));
+    assert_eq!(uart.regs().tx, b"a");
+}
+
+#[test]
+fn send_bytes_polls_once_per_fifo_batch() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(port.fifo_depth(), 16);
+    let before = uart.regs().line_sts_reads;
+    port.send_bytes(&[0x55; 40]);
+    assert_eq!(uart.regs().line_sts_reads - before, 3);
+    assert_eq!(uart.regs().tx, [0x55; 40]);
+}
+
+#[test]
+fn send_integers() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.send_u16_be(0x1234);
+    port.send_u32_le(0x1234_5678);
+    port.send_u64_be(1);
+    assert_eq!(
+        uart.regs().tx,
+        [0x12, 0x34, 0x78, 0x56, 0x34, 0x12, 0, 0, 0, 0, 0, 0, 0, 1]
+    );
+}
+
+#[test]
+fn write_formatted_text() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    writeln!(port.as_write(), "ok {}", 42).unwrap();
+    assert_eq!(uart.regs().tx, b"ok 42\n");
+}
+
+#[test]
+fn try_write_str_writes_up_to_fifo_depth() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    assert_eq!(port.try_write_str("Hello"), Ok(5));
+    assert_eq!(port.try_write_str("0123456789abcdefXYZ"), Ok(16));
+    assert_eq!(uart.regs().tx, b"Hello0123456789abcdef");
+    uart.regs().tx_ready = false;
+    assert!(port.try_write_str("world").is_err());
+}
+
+#[test]
+fn send_rs485_releases_bus_after_transmission() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    let driver_enabled = Cell::new(false);
+    port.send_rs485(
+        b"ping",
+        || {
+            assert!(uart.regs().tx.is_empty());
+            driver_enabled.set(true);
+        },
+        || {
+            assert_eq!(uart.regs().tx, b"ping");
+            driver_enabled.set(false);
+        },
+    );
+    assert!(!driver_enabled.get());
+}
+
+#[test]
+fn multidrop_address_and_data() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.send_address(0x12);
+    assert_eq!(uart.regs().line_ctrl, 0b0010_1011);
+    for &data in b"go" {
+        port.send_data(data);
+    }
+    assert_eq!(uart.regs().line_ctrl, 0b0011_1011);
+    assert_eq!(uart.regs().tx, b"\x12go");
+}
+
+#[test]
+fn try_receive_timeout_gives_up() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.push_back(42);
+    assert_eq!(port.try_receive_timeout(1000), Ok(42));
+    assert!(port.try_receive_timeout(1000).is_err());
+}
+
+#[test]
+fn receive_into_reads_available_bytes() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"abc");
+    let mut buf = [0; 8];
+    assert_eq!(port.receive_into(&mut buf), 3);
+    assert_eq!(&buf[..3], b"abc");
+    assert_eq!(port.receive_into(&mut buf), 0);
+}
+
+#[test]
+fn receive_into_checked_reports_overrun() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.push_back(b'x');
+    uart.regs().errors = LineStsFlags::OVERRUN_ERROR;
+    let mut buf = [0; 4];
+    assert_eq!(
+        port.receive_into_checked(&mut buf),
+        ReadResult {
+            count: 1,
+            overran: true,
+        }
+    );
+    assert_eq!(buf[0], b'x');
+}
+
+#[test]
+fn receive_until_stops_at_terminator_or_full_buffer() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"ab\ncdef");
+    let mut line = [0; 80];
+    let len = port.receive_until(b'\n', &mut line);
+    assert_eq!(&line[..len], b"ab\n");
+    let mut buf = [0; 2];
+    assert_eq!(port.receive_until(b'\n', &mut buf), 2);
+    assert_eq!(&buf, b"cd");
+}
+
+#[test]
+fn byte_iterators() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"abcdef");
+    let mut header = [0; 4];
+    header
+        .iter_mut()
+        .zip(port.bytes())
+        .for_each(|(b, data)| *b = data);
+    assert_eq!(&header, b"abcd");
+    assert_eq!(port.try_bytes().collect::<Vec<_>>(), b"ef");
+    assert_eq!(port.try_bytes().count(), 0);
+}
+
+#[test]
+fn drain_receive_fifo_leaves_rest_pending() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    uart.regs().rx.extend(b"xyz");
+    let mut buf = [0; 2];
+    assert_eq!(port.drain_receive_fifo(&mut buf), 2);
+    assert_eq!(&buf, b"xy");
+    assert_eq!(uart.regs().rx, b"z");
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn receive_into_vec_lowers_rts_when_full() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.set_flow_control(FlowControl::RtsCts);
+    uart.regs().rx.extend(b"abcdef");
+    let mut v = heapless::Vec::<u8, 4>::new();
+    assert_eq!(port.receive_into_vec(&mut v), 4);
+    assert_eq!(&v, b"abcd");
+    assert_eq!(uart.regs().modem_ctrl & ModemCtrlFlags::RTS.bits(), 0);
+}
+
+#[test]
+fn enable_and_disable_interrupts() {
+    let uart = MockUart::new();
+    let mut port = uart.init_port();
+    port.enable_interrupts(IntEnFlags::SENT);
+    assert_eq!(uart.regs().int_en, 0b0000_0011);
+    port.disable_interrupts(IntEnFlags::RECEIVED);
+    assert_eq!(uart.regs().int_en, 0b0000_0010);
+    assert_eq!(port.enabled_interrupts(), IntEnFlags::SENT);
+}
+
+#[cfg(feature = "async")]
+mod asynch {
+    use core::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll, Waker},
+    };
+
+    use super::{
+        std::{sync::Arc, task::Wake},
+        MockUart,
+    };
+    use crate::{AsyncSerialPort, IntEnFlags, IoError, SerialWaker, Uart16550};
+
+    /// Counts how often it was woken.
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl CountingWaker {
+        fn count(&self) -> usize {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn interrupts_stay_masked_until_task_waits_again() {
+        static WAKER: SerialWaker = SerialWaker::new();
+
+        let uart = MockUart::new();
+        let mut port = AsyncSerialPort::new(uart.init_port(), &WAKER);
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0; 16];
+        assert_eq!(port.poll_read(&mut cx, &mut buf), Poll::Pending);
+        assert_eq!(uart.regs().int_en, IntEnFlags::RECEIVED.bits());
+
+        // A spurious interrupt masks the UART, so the task enables it again
+        WAKER.on_interrupt(&mut uart.port());
+        assert_eq!(uart.regs().int_en, 0);
+        assert_eq!(counter.count(), 1);
+        assert_eq!(port.poll_read(&mut cx, &mut buf), Poll::Pending);
+        assert_eq!(uart.regs().int_en, IntEnFlags::RECEIVED.bits());
+
+        uart.regs().rx.push_back(b'a');
+        WAKER.on_interrupt(&mut uart.port());
+        assert_eq!(uart.regs().int_en, 0);
+        assert_eq!(counter.count(), 2);
+        assert_eq!(port.poll_read(&mut cx, &mut buf), Poll::Ready(1));
+        assert_eq!(uart.regs().int_en, 0);
+        assert!(port.inner().enabled_interrupts().is_empty());
+    }
+
+    #[test]
+    fn poll_flush_waits_for_shift_register() {
+        static WAKER: SerialWaker = SerialWaker::new();
+
+        let uart = MockUart::new();
+        let mut port = AsyncSerialPort::new(uart.init_port(), &WAKER);
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        uart.regs().tx_ready = false;
+        uart.regs().tx_idle = false;
+        assert_eq!(port.poll_flush(&mut cx), Poll::Pending);
+        assert!(port.inner().enabled_interrupts().contains(IntEnFlags::SENT));
+        assert_eq!(counter.count(), 0);
+
+        // No interrupt signals the end of the last byte, so the task polls again
+        uart.regs().tx_ready = true;
+        assert_eq!(port.poll_flush(&mut cx), Poll::Pending);
+        assert!(!port.inner().enabled_interrupts().contains(IntEnFlags::SENT));
+        assert_eq!(counter.count(), 1);
+
+        uart.regs().tx_idle = true;
+        assert_eq!(port.poll_flush(&mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn poll_read_waits_for_data() {
+        static WAKER: SerialWaker = SerialWaker::new();
+
+        let uart = MockUart::new();
+        let mut port = AsyncSerialPort::new(uart.init_port(), &WAKER);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut buf = [0; 16];
+        assert_eq!(port.poll_read(&mut cx, &mut buf), Poll::Pending);
+        uart.regs().rx.extend(b"abc");
+        assert_eq!(port.poll_read(&mut cx, &mut buf), Poll::Ready(3));
+        assert_eq!(&buf[..3], b"abc");
+    }
+
+    #[test]
+    fn poll_write_fails_while_tx_disabled() {
+        static WAKER: SerialWaker = SerialWaker::new();
+
+        let uart = MockUart::new();
+        let mut port = AsyncSerialPort::new(uart.init_port(), &WAKER);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(port.poll_write(&mut cx, b"a"), Poll::Ready(Ok(1)));
+        port.inner().set_tx_enabled(false);
+        assert_eq!(
+            port.poll_write(&mut cx, b"b"),
+            Poll::Ready(Err(IoError::TxDisabled))
+        );
+        assert_eq!(uart.regs().tx, b"a");
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+mod io {
+    use embedded_io::BufRead;
+
+    use super::MockUart;
+    use crate::BufferedSerialPort;
+
+    #[test]
+    fn single_byte_reads_keep_rts_raised() {
+        use embedded_io::Read;
+
+        use crate::{FlowControl, ModemCtrlFlags, Uart16550};
+
+        let uart = MockUart::new();
+        let mut port = uart.init_port();
+        port.set_flow_control(FlowControl::RtsCts);
+        uart.regs().rx.extend(b"ab");
+        let mut buf = [0; 1];
+        for &expected in b"ab" {
+            assert_eq!(port.read(&mut buf).unwrap(), 1);
+            assert_eq!(buf[0], expected);
+            assert!(port.modem_control().contains(ModemCtrlFlags::RTS));
+        }
+    }
+
+    #[test]
+    fn buffered_port_consumes_partially() {
+        let uart = MockUart::new();
+        let mut port = BufferedSerialPort::new(uart.init_port());
+        uart.regs().rx.extend(b"abc");
+        assert_eq!(port.fill_buf().unwrap(), b"abc");
+        port.consume(1);
+        assert_eq!(port.fill_buf().unwrap(), b"bc");
+    }
+}
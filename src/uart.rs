@@ -0,0 +1,1829 @@
+use core::fmt;
+
+use crate::{
+    closest_divisor,
+    config::{
+        ENHANCED_REGISTER_LCR, FCR_CLEAR_RX, FCR_CLEAR_TX, FCR_ENABLE, LCR_BREAK,
+        LCR_DATA_BITS_MASK, LCR_PARITY_MASK, LCR_STOP_BITS,
+    },
+    divisor_for,
+    driver::{Driver, DriverExt, Register, DATA, FIFO_CTRL, LINE_CTRL, MODEM_STS, SCRATCH},
+    Bytes, ConfigError, DataBits, FifoStatus, FifoTrigger, FlowControl, Iir, IntEnFlags,
+    InterruptCause, LineConfig, LineStsFlags, LoopbackDiagnosis, LoopbackTestError, ModemCtrlFlags,
+    ModemStatusEvent, ModemStsFlags, Parity, ReadResult, ReceiveError, RegisterSnapshot, SendError,
+    StopBits, TryBytes, UartVariant, WouldBlockError, DEFAULT_BAUD_RATE,
+    LOOPBACK_DIAGNOSE_PATTERNS, LOOPBACK_TEST_BYTE, LOOPBACK_TEST_SPINS,
+};
+
+/// Driver for 16550 compatible UARTs.
+///
+/// The whole driver is implemented once on top of the register accesses of the port types,
+/// [`MmioSerialPort`](crate::MmioSerialPort), the port-mapped `SerialPort` and
+/// [`NullSerialPort`](crate::NullSerialPort). So the trait must be in scope to use them:
+///
+/// ```no_run
+/// use uart_16550::{MmioSerialPort, Uart16550};
+///
+/// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+/// serial_port.init();
+/// serial_port.send(b'a');
+/// ```
+///
+/// Code such as a logger can be written once for any 16550, either generically or through
+/// `&mut dyn Uart16550`:
+///
+/// ```no_run
+/// use uart_16550::{MmioSerialPort, Uart16550};
+///
+/// fn log(uart: &mut dyn Uart16550, message: &str) {
+///     uart.send_bytes(message.as_bytes());
+///     uart.flush();
+/// }
+///
+/// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+/// serial_port.init();
+/// log(&mut serial_port, "hi");
+/// ```
+///
+/// The non-blocking methods can be used the same way, e.g. with the port-mapped `SerialPort`:
+///
+/// ```no_run
+/// # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+/// # fn main() {
+/// use uart_16550::{SerialPort, Uart16550, WouldBlockError};
+///
+/// fn echo<U: Uart16550 + ?Sized>(uart: &mut U) -> Result<(), WouldBlockError> {
+///     let byte = uart.try_receive()?;
+///     uart.try_send_raw(byte)
+/// }
+///
+/// let mut serial_port = unsafe { SerialPort::new(0x3F8) };
+/// serial_port.init();
+/// let uart: &mut dyn Uart16550 = &mut serial_port;
+/// let _ = echo(uart);
+/// # }
+/// # #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+/// # fn main() {}
+/// ```
+///
+/// The trait is sealed. UARTs whose registers are accessed in other ways, e.g. through a
+/// hypervisor call, can implement [`RegisterAccess`](crate::RegisterAccess) instead and
+/// use the driver through [`GenericSerialPort`](crate::GenericSerialPort). Registers that
+/// only need to be protected by a lock are best served by wrapping one of the provided
+/// port types in that lock.
+pub trait Uart16550: fmt::Write + Driver {
+    /// Initializes the UART.
+    ///
+    /// The default configuration of [38400/8-N-1](https://en.wikipedia.org/wiki/8-N-1) is used.
+    ///
+    /// This programs all registers: interrupts are disabled, the line is configured through
+    /// [`configure`](Self::configure), the FIFOs are enabled and cleared, `DTR`, `RTS` and
+    /// `OUT2` are asserted and finally the received data interrupt is enabled. To change the
+    /// line settings of a running port later, use `configure` or the individual setters
+    /// instead, which leave the FIFOs and modem control lines alone.
+    fn init(&mut self) {
+        self.init_polling();
+
+        // Enable interrupts
+        self.enable_interrupts(IntEnFlags::RECEIVED);
+    }
+
+    /// Initializes the UART like [`init`](Self::init), but leaves all interrupts disabled.
+    ///
+    /// Use this when polling the UART, or to enable interrupts only once an interrupt
+    /// handler has been installed.
+    ///
+    /// If the clock frequency set through [`set_clock_frequency`](Self::set_clock_frequency)
+    /// can not generate 38400 baud accurately, the closest baud rate is used instead, which
+    /// is reported by [`current_config`](Self::current_config).
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init_polling();
+    /// assert!(serial_port.enabled_interrupts().is_empty());
+    /// ```
+    fn init_polling(&mut self) {
+        // Disable interrupts
+        self.disable_all_interrupts();
+
+        // Set maximum speed to 38400 bps, data word length to 8 bits,
+        // no parity and one stop bit
+        if self.configure(LineConfig::new()).is_err() {
+            // The clock can not generate 38400 bps accurately, so use the closest baud rate
+            let divisor = closest_divisor(self.state().clock_hz, DEFAULT_BAUD_RATE);
+            let divisor = divisor.clamp(1, u16::MAX.into()) as u16;
+            self.set_divisor_and_line_ctrl(divisor, DataBits::Eight.lcr_bits());
+            self.update_baud(divisor);
+        }
+
+        self.init_fifo_and_modem_ctrl();
+    }
+
+    /// Initializes the UART like [`init`](Self::init), but keeps the baud rate that was
+    /// programmed before, e.g. by the firmware or a bootloader.
+    ///
+    /// Like `init`, this writes the interrupt enable, line control, FIFO control and modem
+    /// control registers, configuring 8-N-1. The divisor latch is only read, to determine
+    /// the baud rate reported by [`current_config`](Self::current_config), and never written.
+    /// The reported baud rate is only correct if the clock frequency is set correctly, see
+    /// [`set_clock_frequency`](Self::set_clock_frequency).
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init_preserve_baud();
+    /// let config = serial_port.current_config();
+    /// ```
+    fn init_preserve_baud(&mut self) {
+        // Disable interrupts
+        self.disable_all_interrupts();
+
+        // Keep the divisor, but set data word length to 8 bits,
+        // no parity and one stop bit
+        let divisor = self.read_divisor();
+        self.update_baud(divisor);
+        self.set_line_ctrl(DataBits::Eight.lcr_bits());
+
+        self.init_fifo_and_modem_ctrl();
+
+        // Enable interrupts
+        self.enable_interrupts(IntEnFlags::RECEIVED);
+    }
+
+    /// Shuts the UART down, as the counterpart to [`init`](Self::init).
+    ///
+    /// This waits until all data has been transmitted, disables all interrupts and clears
+    /// the modem control register. Dropping `DTR` and `RTS` signals a connected device, e.g.
+    /// a modem, that the host is gone, and dropping `OUT2` disconnects the interrupt line on
+    /// PC-compatible hardware.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.shutdown();
+    /// ```
+    fn shutdown(&mut self) {
+        self.flush();
+        self.disable_all_interrupts();
+        self.set_modem_control(ModemCtrlFlags::empty());
+    }
+
+    /// Takes a snapshot of the configuration registers.
+    ///
+    /// The snapshot contains the divisor latch and the line control, interrupt enable, FIFO
+    /// control and modem control registers, as last written through this port.
+    fn capture(&self) -> RegisterSnapshot {
+        let state = self.state();
+        RegisterSnapshot {
+            divisor: state.divisor,
+            baud: state.baud,
+            line_ctrl: state.line_ctrl,
+            int_en: state.int_en,
+            fifo_ctrl: state.fifo_ctrl,
+            modem_ctrl: state.modem_ctrl,
+        }
+    }
+
+    /// Writes the configuration registers from the given snapshot.
+    ///
+    /// Interrupts are disabled while the registers are written and the interrupts of the
+    /// snapshot are enabled last. The FIFOs are not cleared.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_baud_rate(115_200).unwrap();
+    /// let snapshot = serial_port.capture();
+    ///
+    /// // The UART lost its configuration, e.g. through a reset
+    /// serial_port.restore(&snapshot);
+    /// assert_eq!(serial_port.capture(), snapshot);
+    /// ```
+    fn restore(&mut self, snapshot: &RegisterSnapshot) {
+        self.disable_all_interrupts();
+        self.set_divisor_and_line_ctrl(snapshot.divisor, snapshot.line_ctrl);
+        self.state_mut().baud = snapshot.baud;
+        self.set_fifo_ctrl(snapshot.fifo_ctrl);
+        self.set_modem_control(snapshot.modem_ctrl);
+        self.set_int_en(snapshot.int_en);
+    }
+
+    /// Checks that the UART works by sending a byte in loopback mode.
+    ///
+    /// In loopback mode, the transmitter output is internally connected to the receiver
+    /// input, so the sent byte must be received again. Sending and receiving both give up
+    /// after a bounded number of polls, so a wedged transmitter is reported as a
+    /// [`Timeout`](LoopbackTestError::Timeout) instead of hanging. The modem control
+    /// register is restored afterwards. The receive FIFO should be empty when calling this, e.g. right
+    /// after [`init`](Self::init).
+    fn loopback_test(&mut self) -> Result<(), LoopbackTestError> {
+        self.loopback_test_with_data(&[LOOPBACK_TEST_BYTE])
+    }
+
+    /// Checks that the UART works by sending the given bytes in loopback mode.
+    ///
+    /// Like [`loopback_test`](Self::loopback_test), but every byte of the pattern is sent
+    /// and must be received again, which can reveal stuck data bits that a single byte
+    /// can not. The modem control register is restored afterwards.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// if let Err(err) = serial_port.loopback_test_with_data(&[0x00, 0xFF, 0x55, 0xAA]) {
+    ///     // report err
+    /// }
+    /// ```
+    fn loopback_test_with_data(&mut self, pattern: &[u8]) -> Result<(), LoopbackTestError> {
+        let modem_ctrl = self.modem_control();
+        self.set_modem_control(modem_ctrl | ModemCtrlFlags::LOOPBACK);
+        let mut result = Ok(());
+        for &expected in pattern {
+            if self
+                .send_raw_timeout(expected, LOOPBACK_TEST_SPINS)
+                .is_err()
+            {
+                result = Err(LoopbackTestError::Timeout { expected });
+                break;
+            }
+            result = match self.try_receive_timeout(LOOPBACK_TEST_SPINS) {
+                Ok(received) if received == expected => continue,
+                Ok(received) => Err(LoopbackTestError::Mismatch { expected, received }),
+                Err(WouldBlockError) => Err(LoopbackTestError::Timeout { expected }),
+            };
+            break;
+        }
+        self.set_modem_control(modem_ctrl);
+        result
+    }
+
+    /// Sends the patterns `0x00`, `0xFF`, `0xAA` and `0x55` in loopback mode and reports
+    /// which data bits did not follow them.
+    ///
+    /// Unlike [`loopback_test`](Self::loopback_test), all patterns are sent even if one of
+    /// them fails, so a data bit that is stuck high or low shows up in the result. The modem
+    /// control register is restored afterwards. The receive FIFO should be empty when
+    /// calling this, e.g. right after [`init`](Self::init).
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let diagnosis = serial_port.loopback_diagnose();
+    /// if !diagnosis.is_ok() {
+    ///     // report diagnosis.faulty_bits()
+    /// }
+    /// ```
+    fn loopback_diagnose(&mut self) -> LoopbackDiagnosis {
+        let modem_ctrl = self.modem_control();
+        self.set_modem_control(modem_ctrl | ModemCtrlFlags::LOOPBACK);
+        let mut diagnosis = LoopbackDiagnosis::new();
+        for &sent in &LOOPBACK_DIAGNOSE_PATTERNS {
+            if self.send_raw_timeout(sent, LOOPBACK_TEST_SPINS).is_err() {
+                diagnosis.record_timeout();
+                continue;
+            }
+            match self.try_receive_timeout(LOOPBACK_TEST_SPINS) {
+                Ok(received) => diagnosis.record(sent, received),
+                Err(WouldBlockError) => diagnosis.record_timeout(),
+            }
+        }
+        self.set_modem_control(modem_ctrl);
+        diagnosis
+    }
+
+    /// Applies the given line configuration.
+    ///
+    /// The baud rate, word length, parity and stop bits are programmed at once, so no
+    /// intermediate configuration is visible on the line. Returns an error if the
+    /// configuration is not supported, in which case the port is left untouched.
+    ///
+    /// Only the divisor latch and the line control register are written, so this can be used
+    /// to change the settings of a running port without clearing the FIFOs or glitching the
+    /// modem control lines.
+    ///
+    /// ```no_run
+    /// use uart_16550::{LineConfig, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.configure(LineConfig::new().baud(115_200)).unwrap();
+    /// ```
+    fn configure(&mut self, config: LineConfig) -> Result<(), ConfigError> {
+        let divisor = config.divisor(self.state().clock_hz)?;
+        let line_ctrl = config.line_ctrl()?;
+        self.set_divisor_and_line_ctrl(divisor, line_ctrl);
+        self.state_mut().baud = config.baud_rate();
+        Ok(())
+    }
+
+    /// Returns the line configuration that is currently in use.
+    ///
+    /// The divisor latch and line control register can not be read back reliably, so this
+    /// reflects the settings that were last written through this port, starting from the
+    /// defaults applied by [`init`](Self::init).
+    ///
+    /// ```no_run
+    /// use uart_16550::{LineConfig, MmioSerialPort, Parity, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_baud_rate(115_200).unwrap();
+    /// serial_port.set_parity(Parity::Even);
+    /// assert_eq!(
+    ///     serial_port.current_config(),
+    ///     LineConfig::new().baud(115_200).parity(Parity::Even)
+    /// );
+    /// ```
+    fn current_config(&self) -> LineConfig {
+        LineConfig::from_lcr(self.state().baud, self.state().line_ctrl)
+    }
+
+    /// Sets the baud rate by reprogramming the divisor latch.
+    ///
+    /// The divisor is computed from the input clock set with
+    /// [`set_clock_frequency`](Self::set_clock_frequency). The other line
+    /// settings are preserved. Returns an error if the requested baud rate can not be
+    /// generated accurately enough from the input clock.
+    fn set_baud_rate(&mut self, baud: u32) -> Result<(), ConfigError> {
+        let divisor = divisor_for(self.state().clock_hz, baud)?;
+        self.set_divisor_and_line_ctrl(divisor, self.state().line_ctrl);
+        self.state_mut().baud = baud;
+        Ok(())
+    }
+
+    /// Sets the number of data bits per character.
+    ///
+    /// The parity and stop bit settings are preserved.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// ```
+    fn set_data_bits(&mut self, bits: DataBits) {
+        self.set_line_ctrl((self.state().line_ctrl & !LCR_DATA_BITS_MASK) | bits.lcr_bits());
+    }
+
+    /// Sets the parity mode.
+    ///
+    /// The word length and stop bit settings are preserved.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Parity, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_parity(Parity::Even);
+    /// assert_eq!(serial_port.parity(), Parity::Even);
+    /// ```
+    fn set_parity(&mut self, parity: Parity) {
+        self.set_line_ctrl((self.state().line_ctrl & !LCR_PARITY_MASK) | parity.lcr_bits());
+    }
+
+    /// Returns the configured parity mode.
+    fn parity(&self) -> Parity {
+        Parity::from_lcr(self.state().line_ctrl)
+    }
+
+    /// Sets the number of stop bits.
+    ///
+    /// The word length and parity settings are preserved. Since the hardware uses the same
+    /// setting for 1.5 and 2 stop bits, [`StopBits::OneAndHalf`] is only accepted with 5
+    /// data bits and [`StopBits::Two`] only with 6 or more data bits.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, StopBits, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_stop_bits(StopBits::Two).unwrap();
+    /// assert!(serial_port.set_stop_bits(StopBits::OneAndHalf).is_err());
+    /// serial_port.set_stop_bits(StopBits::One).unwrap();
+    ///
+    /// serial_port.set_data_bits(DataBits::Five);
+    /// serial_port.set_stop_bits(StopBits::OneAndHalf).unwrap();
+    /// assert!(serial_port.set_stop_bits(StopBits::Two).is_err());
+    /// ```
+    fn set_stop_bits(&mut self, stop: StopBits) -> Result<(), ConfigError> {
+        let line_ctrl = self.state().line_ctrl;
+        let bits = stop.lcr_bits(line_ctrl)?;
+        self.set_line_ctrl((line_ctrl & !LCR_STOP_BITS) | bits);
+        Ok(())
+    }
+
+    /// Asserts or releases a break condition.
+    ///
+    /// While enabled, the line is held in the spacing state. The duration of the break is
+    /// up to the caller. All other line settings are preserved.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_break(true);
+    /// // Wait for the required duration
+    /// serial_port.set_break(false);
+    /// ```
+    fn set_break(&mut self, enabled: bool) {
+        let line_ctrl = if enabled {
+            self.state().line_ctrl | LCR_BREAK
+        } else {
+            self.state().line_ctrl & !LCR_BREAK
+        };
+        self.set_line_ctrl(line_ctrl);
+    }
+
+    /// Sends a break condition of the given duration.
+    ///
+    /// This waits until all pending data has been transmitted, asserts the break through
+    /// [`set_break`](Self::set_break), waits for `ns` nanoseconds using the given delay and
+    /// clears the break again.
+    ///
+    /// ```no_run
+    /// use embedded_hal::delay::DelayNs;
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// struct SpinDelay;
+    ///
+    /// impl DelayNs for SpinDelay {
+    ///     fn delay_ns(&mut self, ns: u32) {
+    ///         for _ in 0..ns {
+    ///             core::hint::spin_loop();
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.send_break_for(&mut SpinDelay, 1000);
+    /// ```
+    #[cfg(feature = "embedded-hal")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "embedded-hal")))]
+    fn send_break_for(&mut self, delay: &mut impl embedded_hal::delay::DelayNs, ns: u32)
+    where
+        Self: Sized,
+    {
+        self.flush();
+        self.set_break(true);
+        delay.delay_ns(ns);
+        self.set_break(false);
+    }
+
+    /// Reads the divisor latch.
+    ///
+    /// While the divisor latch access bit (DLAB) of the line control register is set, the
+    /// data and interrupt enable registers give access to the low and high byte of the
+    /// divisor instead. The line control register is restored afterwards. Not all chips
+    /// allow reading back the divisor.
+    fn read_divisor(&mut self) -> u16 {
+        let line_ctrl = self.state().line_ctrl;
+        self.write(LINE_CTRL, line_ctrl | 0x80);
+        let divisor = u16::from_le_bytes([self.read(DATA), self.read(Register(1))]);
+        self.write(LINE_CTRL, line_ctrl);
+        divisor
+    }
+
+    /// Writes the divisor latch.
+    ///
+    /// This is a lower-level alternative to [`set_baud_rate`](Self::set_baud_rate), which
+    /// programs the given divisor without any checks. The line control register is restored
+    /// afterwards.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// // 9600 baud with the standard 1.8432 MHz clock
+    /// serial_port.write_divisor(12);
+    /// ```
+    fn write_divisor(&mut self, divisor: u16) {
+        self.set_divisor_and_line_ctrl(divisor, self.state().line_ctrl);
+        self.update_baud(divisor);
+    }
+
+    /// Enables or disables the transmitter in software.
+    ///
+    /// While the transmitter is disabled, [`try_send_raw`](Self::try_send_raw) and the other
+    /// non-blocking send methods return [`WouldBlockError`] regardless of the hardware state.
+    /// The blocking send methods, such as [`send`](Self::send) and
+    /// [`send_bytes`](Self::send_bytes), wait until the transmitter is enabled again, just
+    /// as for a full FIFO, so no data is lost. They must therefore not be called while
+    /// nothing else will enable the transmitter again. The I/O trait implementations fail
+    /// instead of waiting: [`fmt::Write`] returns [`fmt::Error`], and the `embedded-io`,
+    /// `embedded-io-async` and `ufmt` implementations return
+    /// [`IoError::TxDisabled`](crate::IoError::TxDisabled). The hardware is not touched, so
+    /// this can be used to hold off transmission or to simulate a full FIFO.
+    ///
+    /// ```no_run
+    /// use core::fmt::Write;
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_tx_enabled(false);
+    /// assert!(serial_port.try_send_raw(b'a').is_err());
+    /// assert!(write!(serial_port, "b").is_err());
+    /// serial_port.set_tx_enabled(true);
+    /// serial_port.send(b'c');
+    /// ```
+    fn set_tx_enabled(&mut self, enabled: bool) {
+        self.state_mut().tx_enabled = enabled;
+    }
+
+    /// Returns whether the transmitter is enabled in software, see
+    /// [`set_tx_enabled`](Self::set_tx_enabled).
+    fn tx_enabled(&self) -> bool {
+        self.state().tx_enabled
+    }
+
+    /// Enables or disables the translation of special characters in [`send`](Self::send).
+    ///
+    /// Translation is enabled by default. When disabled, `send` behaves like
+    /// [`send_raw`](Self::send_raw), which is useful for binary protocols.
+    fn set_translation(&mut self, enabled: bool) {
+        self.state_mut().translate = enabled;
+    }
+
+    /// Enables or disables resetting the receive FIFO on an overrun.
+    ///
+    /// When enabled, [`try_receive_checked`](Self::try_receive_checked) clears the receive
+    /// FIFO when it reports [`ReceiveError::Overrun`], so that the following reads start
+    /// with fresh data instead of the stale bytes received before the overrun. This is
+    /// disabled by default, so that callers can inspect the remaining bytes themselves.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, ReceiveError, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_reset_on_overrun(true);
+    /// if serial_port.try_receive_checked() == Err(ReceiveError::Overrun) {
+    ///     // The stale bytes were discarded
+    /// }
+    /// ```
+    fn set_reset_on_overrun(&mut self, enabled: bool) {
+        self.state_mut().reset_on_overrun = enabled;
+    }
+
+    /// Enables or disables masking received bytes to the configured word length.
+    ///
+    /// With fewer than 8 data bits, some chips return garbage in the unused upper bits of
+    /// received bytes. When enabled, these bits are cleared by all receive methods. This is
+    /// disabled by default, so the bytes are returned as read.
+    ///
+    /// ```no_run
+    /// use uart_16550::{DataBits, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_data_bits(DataBits::Seven);
+    /// serial_port.set_mask_received(true);
+    /// assert!(serial_port.receive() < 0x80);
+    /// ```
+    fn set_mask_received(&mut self, enabled: bool) {
+        self.state_mut().mask_received = enabled;
+    }
+
+    /// Sets the function that is called while waiting for the UART.
+    ///
+    /// This is called by the blocking methods, such as [`send`](Self::send),
+    /// [`receive`](Self::receive) and [`flush`](Self::flush), and by the methods polling
+    /// with a timeout, where each poll counts as one spin. By default, it is called after
+    /// every poll, see [`set_relax_interval`](Self::set_relax_interval). The default is
+    /// [`core::hint::spin_loop`]. A paravirtualized guest or a cooperative scheduler can
+    /// use this to pause or yield instead of busy-waiting.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// fn relax() {
+    ///     // e.g. yield to the scheduler
+    ///     core::hint::spin_loop();
+    /// }
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_relax(relax);
+    /// serial_port.send(b'a');
+    /// ```
+    fn set_relax(&mut self, relax: fn()) {
+        self.state_mut().relax = relax;
+    }
+
+    /// Sets after how many polls of the UART the relax function is called while waiting.
+    ///
+    /// By default, the function set with [`set_relax`](Self::set_relax) is called on every
+    /// iteration. Where it is expensive, e.g. the `pause` instruction on some CPUs, a larger
+    /// interval lets the UART be polled several times in a tight loop in between. An
+    /// interval of `0` is treated as `1`.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_relax_interval(16);
+    /// serial_port.send(b'a');
+    /// ```
+    fn set_relax_interval(&mut self, interval: u32) {
+        self.state_mut().relax_interval = interval.max(1);
+    }
+
+    /// Sets the number of spin iterations to wait after the baud rate divisor was programmed.
+    ///
+    /// On some real hardware, sending immediately after the divisor was written causes the
+    /// first byte to be corrupted because the baud rate generator has not settled yet. If
+    /// the first character after initialization comes out mangled, increasing this value
+    /// should help. Setting it to `0` disables the delay.
+    fn set_baud_settle_spins(&mut self, spins: u32) {
+        self.state_mut().baud_settle_spins = spins;
+    }
+
+    /// Sets the frequency of the input clock driving the baud rate generator.
+    ///
+    /// The default is the standard 1.8432 MHz crystal of the PC. Many embedded UARTs are
+    /// clocked differently, in which case the frequency must be set before calling
+    /// [`init`](Self::init), [`configure`](Self::configure) or
+    /// [`set_baud_rate`](Self::set_baud_rate), as the divisor is computed from it.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.set_clock_frequency(24_000_000);
+    /// serial_port.init();
+    /// serial_port.set_baud_rate(115_200).unwrap();
+    /// ```
+    fn set_clock_frequency(&mut self, clock_hz: u32) {
+        self.state_mut().clock_hz = clock_hz;
+    }
+
+    /// Reads the raw value of the interrupt identification register.
+    ///
+    /// Reading this register clears a pending transmitter empty interrupt. The FIFO status
+    /// it reports updates [`fifo_depth`](Self::fifo_depth).
+    fn iir_raw(&mut self) -> u8 {
+        self.read_iir().raw()
+    }
+
+    /// Reads and decodes the interrupt identification register.
+    ///
+    /// Reading this register clears a pending transmitter empty interrupt. The FIFO status
+    /// it reports updates [`fifo_depth`](Self::fifo_depth).
+    fn iir(&mut self) -> Iir {
+        self.read_iir()
+    }
+
+    /// Reads the interrupt identification register and returns the cause of the pending
+    /// interrupt with the highest priority.
+    ///
+    /// If the cause is [`InterruptCause::TransmitterEmpty`], reading the register already
+    /// clears that interrupt. All other causes need to be cleared as described on
+    /// [`InterruptCause`], after which a lower priority interrupt may be reported. An
+    /// interrupt handler should therefore call this method in a loop until it returns
+    /// [`InterruptCause::None`]:
+    ///
+    /// ```no_run
+    /// use uart_16550::{InterruptCause, MmioSerialPort, Uart16550};
+    ///
+    /// fn handle_interrupt(serial_port: &mut MmioSerialPort, buf: &mut [u8]) {
+    ///     loop {
+    ///         match serial_port.interrupt_cause() {
+    ///             InterruptCause::None => break,
+    ///             InterruptCause::ReceiverLineStatus => {
+    ///                 serial_port.take_errors();
+    ///             }
+    ///             InterruptCause::ReceivedData | InterruptCause::CharacterTimeout => {
+    ///                 serial_port.drain_receive_fifo(buf);
+    ///             }
+    ///             // Already cleared by reading the interrupt identification register
+    ///             InterruptCause::TransmitterEmpty => {}
+    ///             InterruptCause::ModemStatus => {
+    ///                 serial_port.modem_status();
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn interrupt_cause(&mut self) -> InterruptCause {
+        self.iir().cause()
+    }
+
+    /// Reads the interrupt identification register and returns the state of the FIFOs.
+    ///
+    /// After enabling the FIFOs, this tells whether they actually work. Reading the register
+    /// clears a pending transmitter empty interrupt.
+    ///
+    /// ```no_run
+    /// use uart_16550::{FifoStatus, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// if serial_port.fifo_status() == FifoStatus::Unusable {
+    ///     // Fall back to sending single bytes
+    /// }
+    /// ```
+    fn fifo_status(&mut self) -> FifoStatus {
+        self.iir().fifo_status()
+    }
+
+    /// Acknowledges a pending transmitter empty interrupt without sending data.
+    ///
+    /// The interrupt is cleared by reading the interrupt identification register, which only
+    /// works while it is the pending interrupt with the highest priority, i.e. after all
+    /// receive and line status interrupts were handled. If it was just reported by
+    /// [`interrupt_cause`](Self::interrupt_cause), it is already cleared. Sending a byte
+    /// clears it as well. The interrupt is raised again once the transmitter became empty
+    /// after the next write, so an interrupt handler without more data to send should
+    /// consider disabling [`IntEnFlags::SENT`] instead.
+    fn acknowledge_transmit_interrupt(&mut self) {
+        self.iir_raw();
+    }
+
+    /// Enables the given interrupts.
+    ///
+    /// Interrupts that are already enabled stay enabled.
+    fn enable_interrupts(&mut self, flags: IntEnFlags) {
+        self.set_int_en(self.state().int_en | flags);
+    }
+
+    /// Disables the given interrupts.
+    ///
+    /// All other interrupts stay enabled.
+    fn disable_interrupts(&mut self, flags: IntEnFlags) {
+        self.set_int_en(self.state().int_en - flags);
+    }
+
+    /// Disables all interrupts.
+    fn disable_all_interrupts(&mut self) {
+        self.set_int_en(IntEnFlags::empty());
+    }
+
+    /// Returns the currently enabled interrupts.
+    fn enabled_interrupts(&self) -> IntEnFlags {
+        self.state().int_en
+    }
+
+    /// Sets the receive FIFO interrupt trigger level.
+    ///
+    /// This also enables the FIFOs. Their contents are not cleared.
+    fn set_fifo_trigger(&mut self, trigger: FifoTrigger) {
+        self.set_fifo_ctrl(FCR_ENABLE | trigger.fcr_bits());
+    }
+
+    /// Discards all bytes in the receive FIFO.
+    ///
+    /// The FIFOs stay enabled with the current trigger level, or are enabled if they were
+    /// not. The transmit FIFO is not affected.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.reset_receive_fifo();
+    /// serial_port.reset_transmit_fifo();
+    /// ```
+    fn reset_receive_fifo(&mut self) {
+        self.set_fifo_ctrl(self.state().fifo_ctrl | FCR_ENABLE | FCR_CLEAR_RX);
+    }
+
+    /// Discards all bytes in the transmit FIFO that were not sent yet.
+    ///
+    /// The FIFOs stay enabled with the current trigger level, or are enabled if they were
+    /// not. The receive FIFO is not affected.
+    fn reset_transmit_fifo(&mut self) {
+        self.set_fifo_ctrl(self.state().fifo_ctrl | FCR_ENABLE | FCR_CLEAR_TX);
+    }
+
+    /// Writes the modem control register.
+    ///
+    /// Note that [`ModemCtrlFlags::OUT2`] must stay set for the UART interrupts to reach
+    /// the CPU on most PC-compatible hardware.
+    fn set_modem_control(&mut self, flags: ModemCtrlFlags) {
+        self.set_modem_ctrl(flags);
+    }
+
+    /// Returns the last value written to the modem control register.
+    fn modem_control(&self) -> ModemCtrlFlags {
+        self.state().modem_ctrl
+    }
+
+    /// Raises or lowers the data terminal ready line.
+    ///
+    /// All other modem control bits are preserved.
+    fn set_dtr(&mut self, enabled: bool) {
+        let mut flags = self.modem_control();
+        flags.set(ModemCtrlFlags::DTR, enabled);
+        self.set_modem_control(flags);
+    }
+
+    /// Raises or lowers the request to send line.
+    ///
+    /// All other modem control bits are preserved.
+    fn set_rts(&mut self, enabled: bool) {
+        let mut flags = self.modem_control();
+        flags.set(ModemCtrlFlags::RTS, enabled);
+        self.set_modem_control(flags);
+    }
+
+    /// Enables or disables the loopback mode.
+    ///
+    /// While enabled, the transmitter output is internally connected to the receiver input,
+    /// so all sent bytes are received again and nothing reaches the line. The modem control
+    /// outputs are looped back to the modem status inputs as well: `DTR`, `RTS`, `OUT1` and
+    /// `OUT2` show up as `DSR`, `CTS`, `RING` and `CARRIER_DETECT`. As `OUT2` no longer
+    /// reaches the external interrupt line, interrupts are not delivered to the CPU on most
+    /// PC-compatible hardware. The mode stays enabled until it is disabled again, all other
+    /// modem control bits are preserved.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_loopback(true);
+    /// serial_port.set_loopback(false);
+    /// ```
+    fn set_loopback(&mut self, enabled: bool) {
+        let mut flags = self.modem_control();
+        flags.set(ModemCtrlFlags::LOOPBACK, enabled);
+        self.set_modem_control(flags);
+    }
+
+    /// Sets the flow control mode.
+    ///
+    /// With [`FlowControl::RtsCts`], the transmit path additionally waits for the remote to
+    /// assert `CTS` before writing to the transmitter. This reads the modem status register,
+    /// which clears its delta bits. [`send_bytes`](Self::send_bytes) checks `CTS` before
+    /// each batch of up to [`fifo_depth`](Self::fifo_depth) bytes. On the receive path,
+    /// `RTS` is lowered when [`receive_into`](Self::receive_into),
+    /// [`drain_receive_fifo`](Self::drain_receive_fifo) or `receive_into_vec` filled a
+    /// non-empty buffer and raised again by the next receive call. Unlike the automatic flow control of
+    /// the 16750, this works on all chips.
+    ///
+    /// ```no_run
+    /// use uart_16550::{FlowControl, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_flow_control(FlowControl::RtsCts);
+    /// // Waits until the remote asserts CTS
+    /// serial_port.send(42);
+    /// ```
+    fn set_flow_control(&mut self, flow_control: FlowControl) {
+        self.state_mut().flow_control = flow_control;
+        if flow_control == FlowControl::RtsCts {
+            self.set_rts(true);
+        }
+    }
+
+    /// Detects the UART chip variant.
+    ///
+    /// This enables the FIFOs, including the 64 byte FIFO of the 16750, and checks which
+    /// of them read back as enabled. Chips without FIFO are told apart by the presence of a
+    /// scratch register. Enabling the FIFOs clears them, so any buffered data is lost. The
+    /// previous FIFO configuration is restored afterwards.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550, UartVariant};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let variant = serial_port.detect_variant();
+    /// ```
+    fn detect_variant(&mut self) -> UartVariant {
+        let line_ctrl = self.state().line_ctrl;
+
+        // The 64 byte FIFO enable bit can only be written while DLAB is set
+        self.write(LINE_CTRL, line_ctrl | 0x80);
+        self.write(FIFO_CTRL, 0xE7);
+        self.write(LINE_CTRL, line_ctrl);
+        let iir = self.iir();
+
+        // Restore the FIFO configuration
+        self.write(LINE_CTRL, line_ctrl | 0x80);
+        self.write(FIFO_CTRL, self.state().fifo_ctrl);
+        self.write(LINE_CTRL, line_ctrl);
+
+        let variant = match iir.fifo_variant() {
+            Some(variant) => variant,
+            None if self.probe() => UartVariant::Uart16450,
+            None => UartVariant::Uart8250,
+        };
+        let state = self.state_mut();
+        state.variant = Some(variant);
+        let working_fifo = matches!(variant, UartVariant::Uart16550A | UartVariant::Uart16750);
+        state.fifo_depth = if working_fifo && state.fifo_ctrl & FCR_ENABLE != 0 {
+            16
+        } else {
+            1
+        };
+        variant
+    }
+
+    /// Enables or disables the automatic hardware flow control of the 16750.
+    ///
+    /// While enabled, the hardware stops transmitting when `CTS` drops and asserts `RTS`
+    /// depending on the receive FIFO level, so the remote is not overrun. This requires a
+    /// 16750, which is checked through [`detect_variant`](Self::detect_variant) unless the
+    /// variant was already detected. An error is returned for all other variants.
+    fn enable_auto_flow_control(&mut self, enabled: bool) -> Result<(), ConfigError> {
+        let mut flags = self.modem_control();
+        if enabled {
+            let variant = match self.state().variant {
+                Some(variant) => variant,
+                None => self.detect_variant(),
+            };
+            if variant != UartVariant::Uart16750 {
+                return Err(ConfigError::UnsupportedVariant);
+            }
+            flags |= ModemCtrlFlags::AUTO_FLOW_CONTROL | ModemCtrlFlags::RTS;
+        } else {
+            flags -= ModemCtrlFlags::AUTO_FLOW_CONTROL;
+        }
+        self.set_modem_control(flags);
+        Ok(())
+    }
+
+    /// Reads the modem status register.
+    ///
+    /// Reading this register clears the delta bits in hardware, so changes are only
+    /// reported once. To not lose changes, this method should not be called speculatively
+    /// in a tight loop.
+    fn modem_status(&mut self) -> ModemStsFlags {
+        ModemStsFlags::from_bits_retain(self.read(MODEM_STS))
+    }
+
+    /// Reads the modem status register and decodes which lines changed.
+    ///
+    /// Like [`modem_status`](Self::modem_status), this clears the delta bits and acknowledges
+    /// a pending modem status interrupt.
+    ///
+    /// ```no_run
+    /// use uart_16550::{IntEnFlags, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.enable_interrupts(IntEnFlags::STATUS_CHANGE);
+    /// let event = serial_port.modem_status_event();
+    /// if event.carrier_detect_changed() && !event.carrier_detect() {
+    ///     // The remote hung up
+    /// }
+    /// ```
+    fn modem_status_event(&mut self) -> ModemStatusEvent {
+        ModemStatusEvent(self.modem_status())
+    }
+
+    /// Reads the register at the given offset, honoring the register stride.
+    ///
+    /// This is an escape hatch for registers that are not modeled by this trait, e.g. vendor
+    /// specific extensions.
+    ///
+    /// # Safety
+    ///
+    /// Reading some registers has side effects, like consuming received data or clearing
+    /// status bits, which can confuse the other methods of this trait. The caller must also
+    /// ensure that the offset is valid for the device.
+    unsafe fn read_register(&mut self, offset: u8) -> u8 {
+        self.read(Register(offset))
+    }
+
+    /// Writes the register at the given offset, honoring the register stride.
+    ///
+    /// This is an escape hatch for registers that are not modeled by this trait, e.g. vendor
+    /// specific extensions.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// unsafe { serial_port.write_register(7, 0x5A) };
+    /// let scratch = unsafe { serial_port.read_register(7) };
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The write bypasses the copies of the write-only registers kept by the port, so a
+    /// write to a register that is also managed by this trait can get lost or cause
+    /// inconsistent settings. The caller must also ensure that the offset is valid for the
+    /// device and that the write does not violate memory safety.
+    unsafe fn write_register(&mut self, offset: u8, value: u8) {
+        self.write(Register(offset), value);
+    }
+
+    /// Runs the given closure with the enhanced feature register (EFR) window enabled.
+    ///
+    /// Many 16550 clones, such as the 16650, 16750 and 16C950, have an enhanced feature
+    /// register, e.g. to enable deeper FIFOs or automatic flow control. It is accessed by
+    /// writing `0xBF` to the line control register, after which the EFR is found at offset
+    /// 2 and can be accessed through [`read_register`](Self::read_register) and
+    /// [`write_register`](Self::write_register). Afterwards, the line control register is
+    /// restored from the value last written through this port.
+    ///
+    /// # Safety
+    ///
+    /// While the window is enabled, the other registers are remapped as well, e.g. the
+    /// divisor latch appears at offsets 0 and 1, so the closure must not call any other
+    /// method that accesses the UART. If the line control register was not restored, e.g.
+    /// because it was changed behind the back of this port, the port stays in the window
+    /// and all further accesses go to the wrong registers. The caller must also ensure that
+    /// the UART has an enhanced feature register, as writing offset 2 otherwise changes the
+    /// FIFO configuration.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// unsafe {
+    ///     serial_port.with_enhanced_register(|port| {
+    ///         // Enable the enhanced functions, e.g. of a 16650
+    ///         let efr = port.read_register(2);
+    ///         port.write_register(2, efr | 1 << 4);
+    ///     });
+    /// }
+    /// ```
+    unsafe fn with_enhanced_register<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R
+    where
+        Self: Sized,
+    {
+        self.write(LINE_CTRL, ENHANCED_REGISTER_LCR);
+        let result = f(self);
+        self.write(LINE_CTRL, self.state().line_ctrl);
+        result
+    }
+
+    /// Reads the scratch register.
+    fn read_scratch(&mut self) -> u8 {
+        self.read(SCRATCH)
+    }
+
+    /// Writes the scratch register.
+    ///
+    /// The scratch register has no effect on the UART and can be used to store one byte.
+    fn write_scratch(&mut self, value: u8) {
+        self.write(SCRATCH, value);
+    }
+
+    /// Checks whether a UART seems to be present.
+    ///
+    /// This writes a few distinct bytes to the scratch register and checks that each of
+    /// them reads back, which is not the case for a floating bus. The previous scratch
+    /// value is restored afterwards. Unlike a loopback test, this does not require the
+    /// UART to be configured. Note that the original 8250 has no scratch register, so it
+    /// is not detected by this method.
+    fn probe(&mut self) -> bool {
+        let saved = self.read_scratch();
+        let present = [0x55, 0xAA, 0x00].iter().all(|&value| {
+            self.write_scratch(value);
+            self.read_scratch() == value
+        });
+        self.write_scratch(saved);
+        present
+    }
+
+    /// Reads the line status register.
+    ///
+    /// Reading this register clears the error bits in hardware, so errors are only reported
+    /// once.
+    fn line_status(&mut self) -> LineStsFlags {
+        self.line_sts()
+    }
+
+    /// Returns whether a received byte is available.
+    ///
+    /// This reads the line status register once, which clears its error bits.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// while serial_port.is_data_available() {
+    ///     let data = serial_port.receive();
+    /// }
+    /// ```
+    #[doc(alias = "available")]
+    fn is_data_available(&mut self) -> bool {
+        self.line_sts().contains(LineStsFlags::INPUT_FULL)
+    }
+
+    /// Returns whether the transmitter can accept a byte.
+    ///
+    /// This reads the line status register once, which clears its error bits. The
+    /// [`tx_enabled`](Self::set_tx_enabled) setting and flow control are not taken into
+    /// account.
+    fn is_transmit_ready(&mut self) -> bool {
+        self.line_sts().contains(LineStsFlags::OUTPUT_EMPTY)
+    }
+
+    /// Returns whether the transmitter is idle, i.e. all data has left the shift register.
+    ///
+    /// Unlike [`is_transmit_ready`](Self::is_transmit_ready), which only checks that the
+    /// transmitter holding register is empty, this tells when the line is really idle, e.g.
+    /// to release an RS-485 bus. This reads the line status register once, which clears its
+    /// error bits.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.send_bytes(b"frame");
+    /// while !serial_port.transmitter_idle() {}
+    /// // Release the bus
+    /// ```
+    fn transmitter_idle(&mut self) -> bool {
+        self.line_sts().contains(LineStsFlags::TRANSMITTER_EMPTY)
+    }
+
+    /// Reads and clears the pending receive errors without consuming any data.
+    ///
+    /// Only the overrun, parity, framing and break bits of the line status are returned,
+    /// which is useful for counting errors. As reading the line status clears these bits,
+    /// errors returned here are no longer reported by
+    /// [`try_receive_checked`](Self::try_receive_checked) and vice versa, so the two should
+    /// not be interleaved arbitrarily.
+    ///
+    /// ```no_run
+    /// use uart_16550::{LineStsFlags, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut parity_errors = 0;
+    /// if serial_port.take_errors().contains(LineStsFlags::PARITY_ERROR) {
+    ///     parity_errors += 1;
+    /// }
+    /// ```
+    fn take_errors(&mut self) -> LineStsFlags {
+        self.line_sts()
+            & (LineStsFlags::OVERRUN_ERROR
+                | LineStsFlags::PARITY_ERROR
+                | LineStsFlags::FRAMING_ERROR
+                | LineStsFlags::BREAK_INTERRUPT)
+    }
+
+    /// Sends a byte on the serial port.
+    ///
+    /// Backspace and delete are translated into a sequence that erases the previous
+    /// character, unless disabled through [`set_translation`](Self::set_translation).
+    fn send(&mut self, data: u8) {
+        match data {
+            8 | 0x7F if self.state().translate => {
+                self.send_raw(8);
+                self.send_raw(b' ');
+                self.send_raw(8);
+            }
+            data => {
+                self.send_raw(data);
+            }
+        }
+    }
+
+    /// Sends a raw byte on the serial port, intended for binary data.
+    ///
+    /// Waits while the transmitter is busy or disabled through
+    /// [`set_tx_enabled`](Self::set_tx_enabled).
+    fn send_raw(&mut self, data: u8) {
+        retry_until_ok!(self, self.try_send_raw(data))
+    }
+
+    /// Sends a raw byte like [`send_raw`](Self::send_raw) and returns how many times it had
+    /// to wait before the byte was accepted.
+    ///
+    /// A count that keeps growing over time hints at a slow or nearly stuck transmitter. Time
+    /// spent waiting for a transmitter disabled through
+    /// [`set_tx_enabled`](Self::set_tx_enabled) is counted as well.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let spins = serial_port.send_raw_counted(42);
+    /// ```
+    fn send_raw_counted(&mut self, data: u8) -> u32 {
+        let mut spins = 0u32;
+        while self.try_send_raw(data).is_err() {
+            self.spin_wait(spins);
+            spins = spins.saturating_add(1);
+        }
+        spins
+    }
+
+    /// Tries to send a raw byte on the serial port, intended for binary data.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn try_send_raw(&mut self, data: u8) -> Result<(), WouldBlockError> {
+        if self.tx_enabled()
+            && self.line_sts().contains(LineStsFlags::OUTPUT_EMPTY)
+            && self.clear_to_send()
+        {
+            self.write(DATA, data);
+            Ok(())
+        } else {
+            Err(WouldBlockError)
+        }
+    }
+
+    /// Tries to send a raw byte on the serial port, polling up to `max_spins` times.
+    ///
+    /// Unlike [`send_raw`](Self::send_raw), this gives up with [`WouldBlockError`] if the
+    /// transmitter does not become ready in time, e.g. because the port is wedged.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// if serial_port.send_raw_timeout(42, 1000).is_err() {
+    ///     // The transmitter is wedged
+    /// }
+    /// ```
+    fn send_raw_timeout(&mut self, data: u8, max_spins: u32) -> Result<(), WouldBlockError> {
+        for spin in 0..max_spins {
+            if self.try_send_raw(data).is_ok() {
+                return Ok(());
+            }
+            self.spin_wait(spin);
+        }
+        Err(WouldBlockError)
+    }
+
+    /// Tries to send a raw byte on the serial port, checking the line for faults first.
+    ///
+    /// Unlike [`try_send_raw`](Self::try_send_raw), this refuses to send while a break
+    /// condition is reported, which indicates a line that is held low, e.g. because it is
+    /// shorted or miswired, and while the line status reads as all ones, as a floating bus
+    /// does. Reading the line status clears its receive error bits.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, SendError, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// match serial_port.try_send_checked(b'a') {
+    ///     Ok(()) => {}
+    ///     Err(SendError::WouldBlock) => {
+    ///         // Try again later
+    ///     }
+    ///     Err(_) => {
+    ///         // Check the wiring
+    ///     }
+    /// }
+    /// ```
+    fn try_send_checked(&mut self, data: u8) -> Result<(), SendError> {
+        let line_sts = self.line_sts();
+        if line_sts == LineStsFlags::all() {
+            return Err(SendError::NoDevice);
+        }
+        if line_sts.contains(LineStsFlags::BREAK_INTERRUPT) {
+            return Err(SendError::Break);
+        }
+        if self.tx_enabled()
+            && line_sts.contains(LineStsFlags::OUTPUT_EMPTY)
+            && self.clear_to_send()
+        {
+            self.write(DATA, data);
+            Ok(())
+        } else {
+            Err(SendError::WouldBlock)
+        }
+    }
+
+    /// Sends raw bytes on the serial port, intended for binary data.
+    ///
+    /// Once the transmitter is empty, a whole FIFO worth of bytes is written without polling
+    /// the line status in between. This is considerably faster than sending the bytes one by
+    /// one.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.send_bytes(b"Hello world!");
+    /// ```
+    fn send_bytes(&mut self, data: &[u8]) {
+        let depth = self.fifo_depth();
+        for chunk in data.chunks(depth) {
+            // Wait until the transmitter is empty, then fill the FIFO
+            self.send_raw(chunk[0]);
+            for &byte in &chunk[1..] {
+                self.write(DATA, byte);
+            }
+        }
+    }
+
+    /// Sends a `u16` in little-endian byte order without any translation.
+    fn send_u16_le(&mut self, value: u16) {
+        self.send_bytes(&value.to_le_bytes());
+    }
+
+    /// Sends a `u16` in big-endian byte order without any translation.
+    fn send_u16_be(&mut self, value: u16) {
+        self.send_bytes(&value.to_be_bytes());
+    }
+
+    /// Sends a `u32` in little-endian byte order without any translation.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.send_u32_le(0x1234_5678);
+    /// ```
+    fn send_u32_le(&mut self, value: u32) {
+        self.send_bytes(&value.to_le_bytes());
+    }
+
+    /// Sends a `u32` in big-endian byte order without any translation.
+    fn send_u32_be(&mut self, value: u32) {
+        self.send_bytes(&value.to_be_bytes());
+    }
+
+    /// Sends a `u64` in little-endian byte order without any translation.
+    fn send_u64_le(&mut self, value: u64) {
+        self.send_bytes(&value.to_le_bytes());
+    }
+
+    /// Sends a `u64` in big-endian byte order without any translation.
+    fn send_u64_be(&mut self, value: u64) {
+        self.send_bytes(&value.to_be_bytes());
+    }
+
+    /// Returns the port as a [`fmt::Write`] trait object.
+    ///
+    /// This allows handing out only the ability to write formatted text, e.g. to another
+    /// module, without giving access to the rest of the port.
+    ///
+    /// ```no_run
+    /// use core::fmt::Write;
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// fn report(out: &mut dyn Write) -> core::fmt::Result {
+    ///     writeln!(out, "ok")
+    /// }
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// report(serial_port.as_write()).unwrap();
+    /// ```
+    fn as_write(&mut self) -> &mut dyn fmt::Write
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Writes as much of the string as fits into the transmitter without waiting.
+    ///
+    /// Returns the number of bytes that were accepted, which may be less than the length of
+    /// the string, or [`WouldBlockError`] if the transmitter was still busy. The remaining
+    /// bytes can be written later, e.g. from the transmitter empty interrupt. Unlike the
+    /// [`fmt::Write`] implementation, this writes the bytes without translation.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut pending = "Hello world!";
+    /// if let Ok(len) = serial_port.try_write_str(pending) {
+    ///     pending = &pending[len..];
+    /// }
+    /// ```
+    fn try_write_str(&mut self, s: &str) -> Result<usize, WouldBlockError> {
+        let data = s.as_bytes();
+        if data.is_empty() {
+            return Ok(0);
+        }
+        self.try_send_raw(data[0])?;
+        let len = data.len().min(self.fifo_depth());
+        for &byte in &data[1..len] {
+            self.write(DATA, byte);
+        }
+        Ok(len)
+    }
+
+    /// Returns the number of bytes that can be written after the transmitter became empty.
+    ///
+    /// This is 16 once the FIFOs were enabled and the interrupt identification register
+    /// confirmed that they work, and 1 otherwise. The FIFO status is checked by
+    /// [`init`](Self::init) and whenever the register is read, e.g. through
+    /// [`fifo_status`](Self::fifo_status) or [`detect_variant`](Self::detect_variant). The 64
+    /// byte FIFO of the 16750 is never enabled by this crate, so 16 is returned for it.
+    /// [`send_bytes`](Self::send_bytes) writes this many bytes between two polls of the
+    /// line status.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// for chunk in b"Hello world!".chunks(serial_port.fifo_depth()) {
+    ///     serial_port.send_bytes(chunk);
+    /// }
+    /// ```
+    fn fifo_depth(&self) -> usize {
+        self.state().fifo_depth
+    }
+
+    /// Waits until all data has been transmitted.
+    ///
+    /// Unlike the transmitter holding register becoming free, which is all that the send
+    /// methods wait for, this also waits for the shift register to become empty. Afterwards,
+    /// all bytes have physically left the UART, so it is safe to reconfigure the port.
+    fn flush(&mut self) {
+        let mut iteration = 0u32;
+        while !self.line_sts().contains(LineStsFlags::TRANSMITTER_EMPTY) {
+            self.spin_wait(iteration);
+            iteration = iteration.wrapping_add(1);
+        }
+    }
+
+    /// Sends raw bytes and waits until the last of them has been transmitted.
+    ///
+    /// This combines [`send_bytes`](Self::send_bytes) and [`flush`](Self::flush), e.g. for
+    /// protocols where a frame must be sent completely before toggling a control line.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.write_all_flushed(b"frame");
+    /// ```
+    fn write_all_flushed(&mut self, data: &[u8]) {
+        self.send_bytes(data);
+        self.flush();
+    }
+
+    /// Sends raw bytes in a half-duplex RS-485 transmission.
+    ///
+    /// The `pre` closure is called before sending, e.g. to assert the driver enable line of
+    /// the transceiver, and `post` after the transmitter became idle, so that the bus is only
+    /// released once the last stop bit has left the UART.
+    ///
+    /// ```no_run
+    /// use core::cell::Cell;
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let driver_enabled = Cell::new(false);
+    /// serial_port.send_rs485(
+    ///     b"ping",
+    ///     || driver_enabled.set(true),
+    ///     || driver_enabled.set(false),
+    /// );
+    /// assert!(!driver_enabled.get());
+    /// ```
+    fn send_rs485(&mut self, data: &[u8], mut pre: impl FnMut(), mut post: impl FnMut())
+    where
+        Self: Sized,
+    {
+        pre();
+        self.write_all_flushed(data);
+        post();
+    }
+
+    /// Sends an address byte on a multidrop bus, with the parity bit set as 9th bit.
+    ///
+    /// Multidrop buses use a 9th data bit to tell address bytes from data bytes. This is
+    /// emulated through mark parity, so the line should be configured without parity. The
+    /// parity mode is only switched after the transmitter became idle, so that bytes that
+    /// are still being sent are not affected. The parity mode is left as is afterwards.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.send_address(0x12);
+    /// for &data in b"go" {
+    ///     serial_port.send_data(data);
+    /// }
+    /// ```
+    fn send_address(&mut self, addr: u8) {
+        send_with_parity(self, addr, Parity::Mark);
+    }
+
+    /// Sends a data byte on a multidrop bus, with the parity bit cleared as 9th bit.
+    ///
+    /// See [`send_address`](Self::send_address). Consecutive data bytes are sent without
+    /// waiting for the transmitter in between, as space parity is then already configured.
+    fn send_data(&mut self, data: u8) {
+        send_with_parity(self, data, Parity::Space);
+    }
+
+    /// Receives a byte on the serial port.
+    ///
+    /// With [`FlowControl::RtsCts`], `RTS` is raised before waiting, as the caller has room
+    /// for the byte. The same applies to all other receive methods.
+    fn receive(&mut self) -> u8 {
+        retry_until_ok!(self, self.try_receive())
+    }
+
+    /// Tries to receive a byte on the serial port.
+    #[cfg_attr(feature = "inline-always", inline(always))]
+    #[cfg_attr(not(feature = "inline-always"), inline)]
+    fn try_receive(&mut self) -> Result<u8, WouldBlockError> {
+        self.update_rts(false);
+        if self.line_sts().contains(LineStsFlags::INPUT_FULL) {
+            let data = self.read_data();
+            Ok(data)
+        } else {
+            Err(WouldBlockError)
+        }
+    }
+
+    /// Tries to receive a byte on the serial port, polling up to `max_spins` times.
+    ///
+    /// Unlike [`receive`](Self::receive), this gives up with [`WouldBlockError`] if no byte
+    /// arrives in time, e.g. because no cable is connected. The wait is measured in spin
+    /// iterations rather than time, as the crate has no access to a clock.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// if serial_port.try_receive_timeout(1000).is_err() {
+    ///     // Nothing connected
+    /// }
+    /// ```
+    fn try_receive_timeout(&mut self, max_spins: u32) -> Result<u8, WouldBlockError> {
+        for spin in 0..max_spins {
+            if let Ok(data) = self.try_receive() {
+                return Ok(data);
+            }
+            self.spin_wait(spin);
+        }
+        Err(WouldBlockError)
+    }
+
+    /// Tries to receive a byte on the serial port, reporting receive errors.
+    ///
+    /// Unlike [`try_receive`](Self::try_receive), this checks the error bits of the line
+    /// status. On an overrun, the error is reported and the byte that is still available is
+    /// returned by the next call, unless the receive FIFO is reset as configured through
+    /// [`set_reset_on_overrun`](Self::set_reset_on_overrun). On parity, framing and break
+    /// errors, the affected byte is discarded.
+    fn try_receive_checked(&mut self) -> Result<u8, ReceiveError> {
+        self.update_rts(false);
+        let line_sts = self.line_sts();
+        if line_sts.contains(LineStsFlags::OVERRUN_ERROR) {
+            if self.state().reset_on_overrun {
+                self.reset_receive_fifo();
+            }
+            return Err(ReceiveError::Overrun);
+        }
+        if !line_sts.contains(LineStsFlags::INPUT_FULL) {
+            return Err(ReceiveError::WouldBlock);
+        }
+
+        let data = self.read_data();
+        if line_sts.contains(LineStsFlags::BREAK_INTERRUPT) {
+            Err(ReceiveError::Break)
+        } else if line_sts.contains(LineStsFlags::FRAMING_ERROR) {
+            Err(ReceiveError::Framing)
+        } else if line_sts.contains(LineStsFlags::PARITY_ERROR) {
+            Err(ReceiveError::Parity)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Receives all immediately available bytes into the given buffer.
+    ///
+    /// Returns the number of bytes that were received. This does not wait for the buffer
+    /// to fill up and returns `0` right away if no data is available. The 16550 does not
+    /// report how many bytes are waiting in the receive FIFO, so the bytes are read while
+    /// data is available and the count is the number that was actually read.
+    #[doc(alias = "drain_available")]
+    fn receive_into(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len();
+        let mut count = 0;
+        for byte in buf {
+            match self.try_receive() {
+                Ok(data) => *byte = data,
+                Err(WouldBlockError) => break,
+            }
+            count += 1;
+        }
+        self.update_rts(len > 0 && count == len);
+        count
+    }
+
+    /// Receives all immediately available bytes into the given buffer like
+    /// [`receive_into`](Self::receive_into), and reports whether data was lost.
+    ///
+    /// The line status is checked for an overrun before every byte, so a parser can tell
+    /// that bytes are missing from the stream and resynchronize. Other receive errors are
+    /// ignored.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut buf = [0; 4];
+    /// let result = serial_port.receive_into_checked(&mut buf);
+    /// if result.overran {
+    ///     // resynchronize the framing
+    /// }
+    /// ```
+    fn receive_into_checked(&mut self, buf: &mut [u8]) -> ReadResult {
+        let len = buf.len();
+        let mut result = ReadResult {
+            count: 0,
+            overran: false,
+        };
+        for byte in buf {
+            let line_sts = self.line_sts();
+            result.overran |= line_sts.contains(LineStsFlags::OVERRUN_ERROR);
+            if !line_sts.contains(LineStsFlags::INPUT_FULL) {
+                break;
+            }
+            *byte = self.read_data();
+            result.count += 1;
+        }
+        self.update_rts(len > 0 && result.count == len);
+        result
+    }
+
+    /// Receives bytes into the given buffer until the terminator is received.
+    ///
+    /// Waits for data until either the terminator was received or the buffer is full and
+    /// returns the number of bytes that were received, including the terminator. Bytes
+    /// with parity, framing or break errors are discarded, as in
+    /// [`try_receive_checked`](Self::try_receive_checked).
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut line = [0; 80];
+    /// let len = serial_port.receive_until(b'\n', &mut line);
+    /// ```
+    fn receive_until(&mut self, terminator: u8, buf: &mut [u8]) -> usize {
+        let len = buf.len();
+        let mut count = 0;
+        self.update_rts(false);
+        for byte in buf {
+            *byte = retry_until_ok!(self, self.try_receive_checked());
+            count += 1;
+            if *byte == terminator {
+                break;
+            }
+        }
+        self.update_rts(count == len);
+        count
+    }
+
+    /// Returns an iterator that receives bytes, waiting until each of them is available.
+    ///
+    /// The iterator never ends, so it should be limited by the caller, e.g. with
+    /// [`Iterator::take`].
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut header = [0; 4];
+    /// header.iter_mut().zip(serial_port.bytes()).for_each(|(b, data)| *b = data);
+    /// ```
+    fn bytes(&mut self) -> Bytes<'_, Self>
+    where
+        Self: Sized,
+    {
+        Bytes::new(self)
+    }
+
+    /// Returns an iterator over the bytes that are already available.
+    ///
+    /// The iterator ends as soon as no more data is available, which makes it suited for
+    /// draining the receive FIFO.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// for byte in serial_port.try_bytes() {
+    ///     // handle byte
+    /// }
+    /// ```
+    fn try_bytes(&mut self) -> TryBytes<'_, Self>
+    where
+        Self: Sized,
+    {
+        TryBytes::new(self)
+    }
+
+    /// Drains the receive FIFO into the given buffer from an interrupt handler.
+    ///
+    /// Returns the number of bytes that were received. The line status is read at least
+    /// once, which acknowledges a pending receiver line status interrupt, and reading the
+    /// data acknowledges the received data and character timeout interrupts. These are
+    /// only acknowledged completely if the buffer did not fill up, otherwise the interrupt
+    /// stays pending and the remaining bytes can be read on the next call. The interrupt
+    /// identification register is not read, so that a pending transmitter empty interrupt
+    /// is not lost. Errors in the received data are ignored.
+    ///
+    /// ```no_run
+    /// use uart_16550::{MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// let mut buf = [0; 16];
+    /// let len = serial_port.drain_receive_fifo(&mut buf);
+    /// ```
+    fn drain_receive_fifo(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while self.line_sts().contains(LineStsFlags::INPUT_FULL) {
+            let Some(byte) = buf.get_mut(count) else {
+                break;
+            };
+            *byte = self.read_data();
+            count += 1;
+        }
+        self.update_rts(!buf.is_empty() && count == buf.len());
+        count
+    }
+
+    /// Receives all immediately available bytes into the given [`heapless::Vec`].
+    ///
+    /// Bytes are pushed until either the vector is full or no more data is available.
+    /// Returns the number of bytes that were pushed. With [`FlowControl::RtsCts`], `RTS` is
+    /// lowered while the vector is full, as in [`receive_into`](Self::receive_into).
+    ///
+    /// ```no_run
+    /// use uart_16550::{FlowControl, MmioSerialPort, Uart16550};
+    ///
+    /// let mut serial_port = unsafe { MmioSerialPort::new(0x1000_0000) };
+    /// serial_port.init();
+    /// serial_port.set_flow_control(FlowControl::RtsCts);
+    /// let mut v = heapless::Vec::<u8, 64>::new();
+    /// serial_port.receive_into_vec(&mut v);
+    /// ```
+    #[cfg(feature = "heapless")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "heapless")))]
+    fn receive_into_vec<const N: usize>(&mut self, v: &mut heapless::Vec<u8, N>) -> usize
+    where
+        Self: Sized,
+    {
+        let len = v.len();
+        while !v.is_full() {
+            if let Ok(data) = self.try_receive() {
+                // Cannot fail since the vector is not full
+                let _ = v.push(data);
+            } else {
+                break;
+            }
+        }
+        self.update_rts(N > 0 && v.is_full());
+        v.len() - len
+    }
+}
+
+/// Sends a raw byte with the given parity, waiting for the transmitter before switching.
+fn send_with_parity<U: Uart16550 + ?Sized>(uart: &mut U, data: u8, parity: Parity) {
+    let line_ctrl = (uart.state().line_ctrl & !LCR_PARITY_MASK) | parity.lcr_bits();
+    if line_ctrl != uart.state().line_ctrl {
+        uart.flush();
+        uart.set_line_ctrl(line_ctrl);
+    }
+    uart.send_raw(data);
+}
+
+/// Writes the string through [`send`](Uart16550::send), as the [`fmt::Write`]
+/// implementations of the port types do.
+pub(crate) fn write_str<U: Uart16550 + ?Sized>(uart: &mut U, s: &str) -> fmt::Result {
+    if !uart.tx_enabled() {
+        return Err(fmt::Error);
+    }
+    for byte in s.bytes() {
+        uart.send(byte);
+    }
+    Ok(())
+}
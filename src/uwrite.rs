@@ -0,0 +1,30 @@
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::SerialPort;
+use crate::{GenericSerialPort, IoError, MmioSerialPort, RegisterAccess, Uart16550};
+
+macro_rules! impl_ufmt {
+    ($port:ty $(, $param:ident: $bound:path)?) => {
+        #[cfg_attr(docsrs, doc(cfg(feature = "ufmt")))]
+        impl$(<$param: $bound>)? ufmt::uWrite for $port {
+            type Error = IoError;
+
+            /// Sends the string through [`send`](Uart16550::send).
+            ///
+            /// Returns [`IoError::TxDisabled`] if the transmitter was disabled in software.
+            fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+                if !self.tx_enabled() {
+                    return Err(IoError::TxDisabled);
+                }
+                for byte in s.bytes() {
+                    self.send(byte);
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl_ufmt!(SerialPort);
+impl_ufmt!(MmioSerialPort);
+impl_ufmt!(GenericSerialPort<R>, R: RegisterAccess);